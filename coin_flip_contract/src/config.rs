@@ -0,0 +1,342 @@
+use crate::*;
+
+use near_sdk::json_types::I128;
+
+// reasons for rejecting fee and limit settings, used as the panic message of the
+// methods that change them
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum ConfigError {
+    FeesAboveBase {
+        total_fee: U128,
+    },
+    MinBetAboveMaxBet {
+        min_bet: U128,
+        max_bet: U128,
+    },
+    ZeroMinBalanceFraction,
+    HouseEdgeOutOfRange {
+        house_edge: I128,
+        min_house_edge: U128,
+        max_house_edge: U128,
+    },
+    InvalidHouseEdgeRange {
+        min_house_edge: U128,
+        max_house_edge: U128,
+    },
+    BankrollFractionAboveBase {
+        max_bet_bankroll_fraction: U128,
+    },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::FeesAboveBase { total_fee } => write!(
+                f,
+                "nft_fee, dev_fee and house_fee add up to {}, above {}",
+                total_fee.0, FRACTIONAL_BASE
+            ),
+            ConfigError::MinBetAboveMaxBet { min_bet, max_bet } => {
+                write!(f, "min_bet {} is above max_bet {}", min_bet.0, max_bet.0)
+            }
+            ConfigError::ZeroMinBalanceFraction => {
+                write!(f, "min_balance_fraction must be greater than 0")
+            }
+            ConfigError::HouseEdgeOutOfRange {
+                house_edge,
+                min_house_edge,
+                max_house_edge,
+            } => write!(
+                f,
+                "House edge of {} is outside the allowed range of {} to {}",
+                house_edge.0, min_house_edge.0, max_house_edge.0
+            ),
+            ConfigError::InvalidHouseEdgeRange {
+                min_house_edge,
+                max_house_edge,
+            } => write!(
+                f,
+                "Invalid house edge range of {} to {}, it must be within 0 to {}",
+                min_house_edge.0, max_house_edge.0, FRACTIONAL_BASE
+            ),
+            ConfigError::BankrollFractionAboveBase {
+                max_bet_bankroll_fraction,
+            } => write!(
+                f,
+                "max_bet_bankroll_fraction of {} cannot be above {}",
+                max_bet_bankroll_fraction.0, FRACTIONAL_BASE
+            ),
+        }
+    }
+}
+
+// fee and limit settings of near bets, fees and win_multiplier in base 10e-5
+// and bet limits in yoctonear
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractConfig {
+    pub nft_fee: U128,
+    pub dev_fee: U128,
+    pub house_fee: U128,
+    pub win_multiplier: U128,
+    pub max_bet: U128,
+    pub min_bet: U128,
+    pub min_balance_fraction: U128,
+}
+
+// ContractConfig fields and max_bet_bankroll_fraction to change,
+// fields left out keep their current value
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(default)]
+pub struct ConfigPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nft_fee: Option<U128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dev_fee: Option<U128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub house_fee: Option<U128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_multiplier: Option<U128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bet: Option<U128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_bet: Option<U128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_balance_fraction: Option<U128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bet_bankroll_fraction: Option<U128>,
+}
+
+impl ConfigPatch {
+    //name and new value of each field set in the patch
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let fields = [
+            ("nft_fee", self.nft_fee),
+            ("dev_fee", self.dev_fee),
+            ("house_fee", self.house_fee),
+            ("win_multiplier", self.win_multiplier),
+            ("max_bet", self.max_bet),
+            ("min_bet", self.min_bet),
+            ("min_balance_fraction", self.min_balance_fraction),
+            ("max_bet_bankroll_fraction", self.max_bet_bankroll_fraction),
+        ];
+        fields
+            .iter()
+            .filter_map(|(name, value)| value.map(|value| (*name, value.0.to_string())))
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HouseEdgeRangeView {
+    pub min_house_edge: U128,
+    pub max_house_edge: U128,
+}
+
+//expected share of each bet kept by the house, nft holders and the dev, base 10e-5.
+//the bet wins with probability PROB/256 and pays (bet - fees) * win_multiplier,
+//a negative edge means players win on average
+pub fn house_edge(nft_fee: u128, dev_fee: u128, house_fee: u128, win_multiplier: u128) -> i128 {
+    let net_share =
+        FRACTIONAL_BASE.saturating_sub(nft_fee.saturating_add(dev_fee).saturating_add(house_fee));
+    let expected_return = net_share
+        .saturating_mul(win_multiplier)
+        .saturating_mul(PROB as u128)
+        / (256 * FRACTIONAL_BASE);
+    FRACTIONAL_BASE as i128 - std::cmp::min(expected_return, i128::MAX as u128) as i128
+}
+
+#[near_bindgen]
+impl SlotMachine {
+    //changes only the settings present in patch, the result must pass validate_config
+    #[payable]
+    pub fn update_config(&mut self, patch: ConfigPatch) -> ContractConfig {
+        self.assert_role(Role::ConfigManager);
+        assert_one_yocto();
+        self.assert_no_config_delay();
+
+        self.internal_apply_config_patch(&patch);
+        CoinFlipEvent::config_update(&patch.fields()).emit();
+        self.get_config()
+    }
+
+    pub fn get_config(&self) -> ContractConfig {
+        ContractConfig {
+            nft_fee: U128(self.nft_fee),
+            dev_fee: U128(self.dev_fee),
+            house_fee: U128(self.house_fee),
+            win_multiplier: U128(self.win_multiplier),
+            max_bet: U128(self.max_bet),
+            min_bet: U128(self.min_bet),
+            min_balance_fraction: U128(self.min_balance_fraction),
+        }
+    }
+
+    //owner declares the house edge every fee and multiplier setting must fall in
+    #[payable]
+    pub fn update_house_edge_range(&mut self, min_house_edge: U128, max_house_edge: U128) {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert_one_yocto();
+
+        self.min_house_edge = min_house_edge.0;
+        self.max_house_edge = max_house_edge.0;
+        self.assert_valid_config();
+        let ft_token_ids: Vec<AccountId> = self.ft_assets.keys().collect();
+        for ft_token_id in ft_token_ids {
+            self.assert_valid_ft_config(&self.internal_ft_asset(&ft_token_id));
+        }
+
+        CoinFlipEvent::config_update(&[
+            ("min_house_edge", self.min_house_edge.to_string()),
+            ("max_house_edge", self.max_house_edge.to_string()),
+        ])
+        .emit();
+    }
+
+    pub fn get_house_edge_range(&self) -> HouseEdgeRangeView {
+        HouseEdgeRangeView {
+            min_house_edge: U128(self.min_house_edge),
+            max_house_edge: U128(self.max_house_edge),
+        }
+    }
+
+    //expected house edge of bets in near, or in ft_token_id when given
+    pub fn get_house_edge(&self, ft_token_id: Option<AccountId>) -> I128 {
+        match ft_token_id {
+            None => I128(house_edge(
+                self.nft_fee,
+                self.dev_fee,
+                self.house_fee,
+                self.win_multiplier,
+            )),
+            Some(ft_token_id) => {
+                let asset = self.internal_ft_asset(&ft_token_id);
+                I128(house_edge(
+                    asset.nft_fee,
+                    asset.dev_fee,
+                    asset.house_fee,
+                    asset.win_multiplier,
+                ))
+            }
+        }
+    }
+}
+
+impl SlotMachine {
+    pub(crate) fn internal_set_config(&mut self, config: &ContractConfig) {
+        self.nft_fee = config.nft_fee.0;
+        self.dev_fee = config.dev_fee.0;
+        self.house_fee = config.house_fee.0;
+        self.win_multiplier = config.win_multiplier.0;
+        self.max_bet = config.max_bet.0;
+        self.min_bet = config.min_bet.0;
+        self.min_balance_fraction = config.min_balance_fraction.0;
+        self.assert_valid_config();
+    }
+
+    pub(crate) fn internal_apply_config_patch(&mut self, patch: &ConfigPatch) {
+        if let Some(max_bet_bankroll_fraction) = patch.max_bet_bankroll_fraction {
+            self.max_bet_bankroll_fraction = max_bet_bankroll_fraction.0;
+        }
+        let current = self.get_config();
+        self.internal_set_config(&ContractConfig {
+            nft_fee: patch.nft_fee.unwrap_or(current.nft_fee),
+            dev_fee: patch.dev_fee.unwrap_or(current.dev_fee),
+            house_fee: patch.house_fee.unwrap_or(current.house_fee),
+            win_multiplier: patch.win_multiplier.unwrap_or(current.win_multiplier),
+            max_bet: patch.max_bet.unwrap_or(current.max_bet),
+            min_bet: patch.min_bet.unwrap_or(current.min_bet),
+            min_balance_fraction: patch
+                .min_balance_fraction
+                .unwrap_or(current.min_balance_fraction),
+        });
+    }
+
+    //checks the near settings, min_balance_fraction and max_bet_bankroll_fraction included
+    pub(crate) fn validate_config(&self) -> Result<(), ConfigError> {
+        if self.min_balance_fraction == 0 {
+            return Err(ConfigError::ZeroMinBalanceFraction);
+        }
+        if self.max_bet_bankroll_fraction > FRACTIONAL_BASE {
+            return Err(ConfigError::BankrollFractionAboveBase {
+                max_bet_bankroll_fraction: U128(self.max_bet_bankroll_fraction),
+            });
+        }
+        self.validate_bet_settings(
+            self.nft_fee,
+            self.dev_fee,
+            self.house_fee,
+            self.win_multiplier,
+            self.max_bet,
+            self.min_bet,
+        )
+    }
+
+    pub(crate) fn validate_ft_config(&self, asset: &FtAsset) -> Result<(), ConfigError> {
+        self.validate_bet_settings(
+            asset.nft_fee,
+            asset.dev_fee,
+            asset.house_fee,
+            asset.win_multiplier,
+            asset.max_bet,
+            asset.min_bet,
+        )
+    }
+
+    pub(crate) fn assert_valid_config(&self) {
+        if let Err(error) = self.validate_config() {
+            env::panic(error.to_string().as_bytes());
+        }
+    }
+
+    pub(crate) fn assert_valid_ft_config(&self, asset: &FtAsset) {
+        if let Err(error) = self.validate_ft_config(asset) {
+            env::panic(error.to_string().as_bytes());
+        }
+    }
+
+    fn validate_bet_settings(
+        &self,
+        nft_fee: u128,
+        dev_fee: u128,
+        house_fee: u128,
+        win_multiplier: u128,
+        max_bet: u128,
+        min_bet: u128,
+    ) -> Result<(), ConfigError> {
+        if self.min_house_edge > self.max_house_edge || self.max_house_edge > FRACTIONAL_BASE {
+            return Err(ConfigError::InvalidHouseEdgeRange {
+                min_house_edge: U128(self.min_house_edge),
+                max_house_edge: U128(self.max_house_edge),
+            });
+        }
+        let total_fee = nft_fee.saturating_add(dev_fee).saturating_add(house_fee);
+        if total_fee > FRACTIONAL_BASE {
+            return Err(ConfigError::FeesAboveBase {
+                total_fee: U128(total_fee),
+            });
+        }
+        if min_bet > max_bet {
+            return Err(ConfigError::MinBetAboveMaxBet {
+                min_bet: U128(min_bet),
+                max_bet: U128(max_bet),
+            });
+        }
+        let house_edge = house_edge(nft_fee, dev_fee, house_fee, win_multiplier);
+        if house_edge < self.min_house_edge as i128 || house_edge > self.max_house_edge as i128 {
+            return Err(ConfigError::HouseEdgeOutOfRange {
+                house_edge: I128(house_edge),
+                min_house_edge: U128(self.min_house_edge),
+                max_house_edge: U128(self.max_house_edge),
+            });
+        }
+        Ok(())
+    }
+}
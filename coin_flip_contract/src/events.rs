@@ -0,0 +1,161 @@
+use crate::*;
+
+use crate::pause::PauseStatusView;
+use crate::roles::Role;
+use crate::stats::GameStatsView;
+use crate::timelock::ConfigProposalView;
+use crate::upgrade::StagedUpgradeView;
+use near_sdk::serde_json;
+use std::collections::HashMap;
+
+pub const EVENT_STANDARD: &str = "coinflip";
+pub const EVENT_STANDARD_VERSION: &str = "1.0.0";
+
+// NEP-297 envelope, logged as EVENT_JSON:{"standard":..,"version":..,"event":..,"data":..}
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a CoinFlipEvent<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum CoinFlipEvent<'a> {
+    Deposit(DepositData),
+    Withdrawal(AmountData),
+    BetPlaced(BetPlacedData),
+    BetResolved(&'a BetResult),
+    BetRefunded(AmountData),
+    FeesAccrued(FeesData),
+    HouseFundsDeposit(AmountData),
+    HouseFundsWithdrawal(AmountData),
+    DevFundsWithdrawal(AmountData),
+    NftRewardsWithdrawal(Vec<NftRewardData>),
+    ConfigUpdate(HashMap<String, String>),
+    ConfigProposed(&'a ConfigProposalView),
+    ConfigProposalCanceled(&'a ConfigProposalView),
+    PauseUpdate(&'a PauseStatusView),
+    EmergencyWithdrawal(AmountData),
+    RoleGranted(RoleData),
+    RoleRevoked(RoleData),
+    OwnershipProposed(OwnershipData),
+    OwnershipProposalCanceled(OwnershipData),
+    OwnershipTransferred(OwnershipData),
+    UpgradeStaged(&'a StagedUpgradeView),
+    UpgradeCanceled(&'a StagedUpgradeView),
+    UpgradeDeployed(&'a StagedUpgradeView),
+    UpgradeFailed(&'a StagedUpgradeView),
+    GameStatsUpdate(GameStatsData<'a>),
+    TransferFailed(TransferData),
+}
+
+// ft_token_id is set when the amounts are in a NEP-141 token, they are in yoctonear otherwise
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DepositData {
+    pub account_id: AccountId,
+    pub amount: U128,
+    pub credits: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AmountData {
+    pub account_id: AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BetPlacedData {
+    pub account_id: AccountId,
+    pub chosen_side: CoinSide,
+    pub bet_size: U128,
+    pub commit_block: U64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeesData {
+    pub nft_fee: U128,
+    pub dev_fee: U128,
+    pub house_fee: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>,
+}
+
+// game stats after a resolved bet, in ft_token_id when set
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GameStatsData<'a> {
+    #[serde(flatten)]
+    pub stats: &'a GameStatsView,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftRewardData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_id: Option<TokenId>,
+    pub account_id: AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoleData {
+    pub account_id: AccountId,
+    pub role: Role,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnershipData {
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransferData {
+    pub kind: TransferKind,
+    pub account_id: AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>,
+}
+
+impl CoinFlipEvent<'_> {
+    //new values of the config fields that changed
+    pub fn config_update(fields: &[(&str, String)]) -> Self {
+        CoinFlipEvent::ConfigUpdate(
+            fields
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+        )
+    }
+
+    pub fn emit(&self) {
+        let log = EventLog {
+            standard: EVENT_STANDARD,
+            version: EVENT_STANDARD_VERSION,
+            event: self,
+        };
+        env::log(format!("EVENT_JSON:{}", serde_json::to_string(&log).unwrap()).as_bytes());
+    }
+}
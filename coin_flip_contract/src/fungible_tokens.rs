@@ -0,0 +1,355 @@
+use crate::*;
+
+use crate::stats::GameStats;
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_sdk::json_types::ValidAccountId;
+use near_sdk::PromiseOrValue;
+
+// msg of ft_on_transfer with which the owner funds the house bankroll of a token
+pub const HOUSE_FUNDS_MSG: &str = "house_funds";
+// most tokens that can be whitelisted, the fixed storage balance covers
+// an account's credits and stats in each of them
+pub const MAX_FT_TOKENS: u64 = 10;
+
+// whitelisted NEP-141 token, bets in it are settled and paid out in the token itself
+// with its own fees and limits. Disabled tokens only accept withdrawals
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct FtAsset {
+    pub enabled: bool,
+    pub nft_fee: u128, // base 10e-5
+    pub dev_fee: u128, // base 10e-5
+    pub house_fee: u128,
+    pub win_multiplier: u128, // base 10e-5
+    pub min_bet: u128,
+    pub max_bet: u128,
+    pub balance: u128,             // tokens received and not paid out
+    pub total_credits: u128,       // sum of all player credits in the token
+    pub pending_liabilities: u128, // sum of max_liability of pending bets in the token
+    pub house_balance: u128,
+    pub dev_balance: u128,
+    pub nft_balance: u128,
+    pub nft_reward_per_token: u128,
+    pub nft_reward_dust: u128,
+    pub game_stats: GameStats,
+}
+
+// fee and limit settings of a token, same units as the near settings
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtAssetConfig {
+    pub nft_fee: U128,
+    pub dev_fee: U128,
+    pub house_fee: U128,
+    pub win_multiplier: U128,
+    pub max_bet: U128,
+    pub min_bet: U128,
+}
+
+// settings of a token a config proposal whitelists, or updates when already accepted
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtTokenConfig {
+    pub ft_token_id: AccountId,
+    pub config: FtAssetConfig,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtAssetView {
+    pub ft_token_id: AccountId,
+    pub enabled: bool,
+    pub config: FtAssetConfig,
+    pub balance: U128,
+    pub total_credits: U128,
+    pub house_balance: U128,
+    pub dev_balance: U128,
+    pub nft_balance: U128,
+}
+
+impl FtAsset {
+    pub fn new(config: &FtAssetConfig) -> Self {
+        let mut asset = Self {
+            enabled: true,
+            nft_fee: 0,
+            dev_fee: 0,
+            house_fee: 0,
+            win_multiplier: 0,
+            min_bet: 0,
+            max_bet: 0,
+            balance: 0,
+            total_credits: 0,
+            pending_liabilities: 0,
+            house_balance: 0,
+            dev_balance: 0,
+            nft_balance: 0,
+            nft_reward_per_token: 0,
+            nft_reward_dust: 0,
+            game_stats: GameStats::default(),
+        };
+        asset.set_config(config);
+        asset
+    }
+
+    pub fn set_config(&mut self, config: &FtAssetConfig) {
+        self.nft_fee = config.nft_fee.0;
+        self.dev_fee = config.dev_fee.0;
+        self.house_fee = config.house_fee.0;
+        self.win_multiplier = config.win_multiplier.0;
+        self.max_bet = config.max_bet.0;
+        self.min_bet = config.min_bet.0;
+    }
+
+    pub fn config(&self) -> FtAssetConfig {
+        FtAssetConfig {
+            nft_fee: U128(self.nft_fee),
+            dev_fee: U128(self.dev_fee),
+            house_fee: U128(self.house_fee),
+            win_multiplier: U128(self.win_multiplier),
+            max_bet: U128(self.max_bet),
+            min_bet: U128(self.min_bet),
+        }
+    }
+
+    pub fn view(&self, ft_token_id: AccountId) -> FtAssetView {
+        FtAssetView {
+            ft_token_id,
+            enabled: self.enabled,
+            config: self.config(),
+            balance: U128(self.balance),
+            total_credits: U128(self.total_credits),
+            house_balance: U128(self.house_balance),
+            dev_balance: U128(self.dev_balance),
+            nft_balance: U128(self.nft_balance),
+        }
+    }
+
+    //everything owed in the token to players, nft holders and the dev
+    pub fn total_liabilities(&self) -> u128 {
+        self.total_credits + self.pending_liabilities + self.nft_balance + self.dev_balance
+    }
+
+    //same split as SlotMachine::accrue_nft_fees, kept per token
+    pub fn accrue_nft_fees(&mut self, amount: u128, nft_mapping_size: u128) {
+        self.nft_balance += amount;
+        let to_distribute = amount + self.nft_reward_dust;
+        if nft_mapping_size == 0 {
+            self.nft_reward_dust = to_distribute;
+            return;
+        }
+        self.nft_reward_per_token += to_distribute / nft_mapping_size;
+        self.nft_reward_dust = to_distribute % nft_mapping_size;
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for SlotMachine {
+    //credits sender_id with the tokens received, the owner funds the house bankroll
+    //instead by sending HOUSE_FUNDS_MSG. Panicking makes the token contract refund the sender
+    fn ft_on_transfer(
+        &mut self,
+        sender_id: ValidAccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let ft_token_id = env::predecessor_account_id();
+        let sender_id: AccountId = sender_id.into();
+        let mut asset = self.internal_ft_asset(&ft_token_id);
+
+        if msg == HOUSE_FUNDS_MSG {
+            assert!(
+                sender_id == self.owner_id,
+                "Only owner can fund the house bankroll"
+            );
+            asset.balance += amount.0;
+            asset.house_balance += amount.0;
+            self.ft_assets.insert(&ft_token_id, &asset);
+
+            CoinFlipEvent::HouseFundsDeposit(AmountData {
+                account_id: sender_id,
+                amount,
+                ft_token_id: Some(ft_token_id),
+            })
+            .emit();
+        } else {
+            assert!(
+                msg.is_empty(),
+                "msg must be empty to deposit credits or {} to fund the house bankroll",
+                HOUSE_FUNDS_MSG
+            );
+            self.assert_not_paused(PausableOperation::Deposits);
+            assert!(asset.enabled, "Token {} is disabled", ft_token_id);
+            self.assert_registered(&sender_id);
+            let min_deposit = asset.min_bet / self.min_balance_fraction;
+            assert!(
+                amount.0 > min_deposit,
+                "Minimum accepted deposit is {}",
+                min_deposit
+            );
+            asset.balance += amount.0;
+            self.ft_assets.insert(&ft_token_id, &asset);
+            let credits = self.internal_add_credits(&sender_id, Some(&ft_token_id), amount.0);
+
+            CoinFlipEvent::Deposit(DepositData {
+                account_id: sender_id,
+                amount,
+                credits: U128(credits),
+                ft_token_id: Some(ft_token_id),
+            })
+            .emit();
+        }
+        PromiseOrValue::Value(U128(0))
+    }
+}
+
+#[near_bindgen]
+impl SlotMachine {
+    //whitelists a NEP-141 token to be deposited and bet with
+    #[payable]
+    pub fn add_ft_token(&mut self, ft_token_id: AccountId, config: FtAssetConfig) {
+        self.assert_role(Role::ConfigManager);
+        assert_one_yocto();
+        self.assert_no_config_delay();
+        assert!(
+            self.ft_assets.get(&ft_token_id).is_none(),
+            "Token {} is already accepted",
+            ft_token_id
+        );
+
+        self.internal_set_ft_token(&FtTokenConfig {
+            ft_token_id,
+            config,
+        });
+    }
+
+    //new fees and limits apply to bets placed from now on
+    #[payable]
+    pub fn update_ft_token(&mut self, ft_token_id: AccountId, config: FtAssetConfig) {
+        self.assert_role(Role::ConfigManager);
+        assert_one_yocto();
+        self.assert_no_config_delay();
+        self.internal_ft_asset(&ft_token_id);
+
+        self.internal_set_ft_token(&FtTokenConfig {
+            ft_token_id,
+            config,
+        });
+    }
+
+    //stops deposits and bets in ft_token_id, pending bets can still be resolved
+    //and players can still retrieve their credits
+    #[payable]
+    pub fn disable_ft_token(&mut self, ft_token_id: AccountId) {
+        self.internal_set_ft_token_enabled(ft_token_id, false);
+    }
+
+    #[payable]
+    pub fn enable_ft_token(&mut self, ft_token_id: AccountId) {
+        self.internal_set_ft_token_enabled(ft_token_id, true);
+    }
+
+    pub fn get_ft_token(&self, ft_token_id: AccountId) -> Option<FtAssetView> {
+        self.ft_assets
+            .get(&ft_token_id)
+            .map(|asset| asset.view(ft_token_id))
+    }
+
+    pub fn get_ft_tokens(&self) -> Vec<FtAssetView> {
+        self.ft_assets
+            .iter()
+            .map(|(ft_token_id, asset)| asset.view(ft_token_id))
+            .collect()
+    }
+}
+
+impl SlotMachine {
+    //updates the settings of an accepted token, whitelists it otherwise
+    pub(crate) fn internal_set_ft_token(&mut self, ft_token: &FtTokenConfig) {
+        assert!(
+            env::is_valid_account_id(ft_token.ft_token_id.as_bytes()),
+            "Invalid token contract account"
+        );
+        let asset = match self.ft_assets.get(&ft_token.ft_token_id) {
+            Some(mut asset) => {
+                asset.set_config(&ft_token.config);
+                asset
+            }
+            None => {
+                assert!(
+                    self.ft_assets.len() < MAX_FT_TOKENS,
+                    "At most {} tokens can be whitelisted",
+                    MAX_FT_TOKENS
+                );
+                FtAsset::new(&ft_token.config)
+            }
+        };
+        self.assert_valid_ft_config(&asset);
+        self.ft_assets.insert(&ft_token.ft_token_id, &asset);
+        emit_ft_config_update(&ft_token.ft_token_id, &ft_token.config);
+    }
+
+    fn internal_set_ft_token_enabled(&mut self, ft_token_id: AccountId, enabled: bool) {
+        self.assert_role(Role::ConfigManager);
+        assert_one_yocto();
+        let mut asset = self.internal_ft_asset(&ft_token_id);
+
+        asset.enabled = enabled;
+        self.ft_assets.insert(&ft_token_id, &asset);
+
+        CoinFlipEvent::config_update(&[
+            ("ft_token_id", ft_token_id),
+            ("enabled", enabled.to_string()),
+        ])
+        .emit();
+    }
+
+    pub(crate) fn internal_ft_asset(&self, ft_token_id: &AccountId) -> FtAsset {
+        match self.ft_assets.get(ft_token_id) {
+            Some(asset) => asset,
+            None => env::panic(format!("Token {} is not accepted", ft_token_id).as_bytes()),
+        }
+    }
+
+    //nft fees accrued to token_id in ft_token_id and not claimed yet
+    pub(crate) fn ft_nft_claimable(&self, ft_token_id: &AccountId, token_id: &TokenId) -> u128 {
+        let asset = self.internal_ft_asset(ft_token_id);
+        let paid = self
+            .ft_nft_rewards_paid
+            .get(&(ft_token_id.clone(), token_id.clone()))
+            .unwrap_or(0);
+        std::cmp::min(
+            asset.nft_reward_per_token.saturating_sub(paid),
+            asset.nft_balance,
+        )
+    }
+
+    pub(crate) fn internal_claim_ft_nft_rewards(
+        &mut self,
+        ft_token_id: &AccountId,
+        token_id: &TokenId,
+    ) -> u128 {
+        let amount = self.ft_nft_claimable(ft_token_id, token_id);
+        if amount > 0 {
+            let mut asset = self.internal_ft_asset(ft_token_id);
+            self.ft_nft_rewards_paid.insert(
+                &(ft_token_id.clone(), token_id.clone()),
+                &asset.nft_reward_per_token,
+            );
+            asset.nft_balance -= amount;
+            self.ft_assets.insert(ft_token_id, &asset);
+        }
+        amount
+    }
+}
+
+fn emit_ft_config_update(ft_token_id: &AccountId, config: &FtAssetConfig) {
+    CoinFlipEvent::config_update(&[
+        ("ft_token_id", ft_token_id.clone()),
+        ("nft_fee", config.nft_fee.0.to_string()),
+        ("dev_fee", config.dev_fee.0.to_string()),
+        ("house_fee", config.house_fee.0.to_string()),
+        ("win_multiplier", config.win_multiplier.0.to_string()),
+        ("max_bet", config.max_bet.0.to_string()),
+        ("min_bet", config.min_bet.0.to_string()),
+    ])
+    .emit();
+}
@@ -1,23 +1,196 @@
-use std::convert::TryInto;
-
 use borsh::{BorshDeserialize, BorshSerialize};
+use near_contract_standards::non_fungible_token::TokenId;
 use near_sdk::borsh;
+use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    collections::LookupMap, env, json_types::U128, near_bindgen, utils::assert_one_yocto,
+    collections::{LookupMap, UnorderedMap, UnorderedSet},
+    env,
+    json_types::{U128, U64},
+    near_bindgen,
+    utils::assert_one_yocto,
     AccountId, Balance, Promise,
 };
 
+mod config;
+mod events;
+mod fungible_tokens;
+mod migrate;
+mod nft_rewards;
+mod pause;
+mod roles;
+mod stats;
+mod storage;
+mod timelock;
+mod transfers;
+mod upgrade;
+
+use config::ContractConfig;
+use events::{AmountData, BetPlacedData, CoinFlipEvent, DepositData, FeesData};
+use fungible_tokens::FtAsset;
+use migrate::V1Migration;
+use nft_rewards::{ft_nft_rewards_paid_prefix, nft_rewards_paid_prefix, NftDistributionRound};
+use pause::{PausableOperation, PauseFlags, PauseStatusView};
+use roles::Role;
+use stats::{BetHistory, GameStats, PlayerStats};
+use timelock::ConfigProposal;
+use transfers::TransferKind;
+use upgrade::StagedUpgrade;
+
 #[global_allocator]
 static ALLOC: near_sdk::wee_alloc::WeeAlloc = near_sdk::wee_alloc::WeeAlloc::INIT;
 
 // const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
 const PROB: u8 = 128;
 const FRACTIONAL_BASE: u128 = 100_000;
+// blocks that must pass between placing a bet and resolving it
+const RESOLVE_DELAY_BLOCKS: u64 = 2;
+// blocks after which an unresolved bet can only be refunded
+const BET_TIMEOUT_BLOCKS: u64 = 3_600;
+// most accounts returned by a single get_credit_holders call
+const MAX_CREDIT_HOLDERS_LIMIT: u64 = 100;
+
+// bet committed by a player, waiting for a later block to be resolved
+// fees and payout are fixed when the bet is placed
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PendingBet {
+    pub bet_type: bool,
+    pub bet_size: u128,
+    pub nft_cut: u128,
+    pub dev_cut: u128,
+    pub house_cut: u128,
+    pub win_payout: u128,
+    pub commit_block: u64,
+    pub ft_token_id: Option<AccountId>, // None for bets in near
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "lowercase")]
+pub enum CoinSide {
+    Heads,
+    Tails,
+}
+
+impl From<bool> for CoinSide {
+    //bet_type heads == true, tails == false
+    fn from(bet_type: bool) -> Self {
+        if bet_type {
+            CoinSide::Heads
+        } else {
+            CoinSide::Tails
+        }
+    }
+}
+
+// outcome of a resolved bet, all values in yoctonear
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BetResult {
+    pub account_id: AccountId,
+    pub chosen_side: CoinSide,
+    pub landed_side: CoinSide,
+    pub won: bool,
+    pub bet_size: U128,
+    pub nft_fee: U128,
+    pub dev_fee: U128,
+    pub house_fee: U128,
+    pub payout: U128,
+    pub credits: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>,
+}
+
+// reasons for rejecting a bet, returned by validate_bet and used as play's panic message
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum BetError {
+    ContractPaused { reason: String },
+    TokenNotAccepted { ft_token_id: AccountId },
+    TokenDisabled { ft_token_id: AccountId },
+    PendingBetExists,
+    BelowMinBet { min_bet: U128 },
+    AboveMaxBet { max_bet: U128 },
+    InsufficientCredits { credits: U128, bet_size: U128 },
+    HouseCannotCover { payout: U128, available: U128 },
+}
+
+impl std::fmt::Display for BetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BetError::ContractPaused { reason } => {
+                write!(f, "Plays are paused by owner: {}", reason)
+            }
+            BetError::TokenNotAccepted { ft_token_id } => {
+                write!(f, "Token {} is not accepted", ft_token_id)
+            }
+            BetError::TokenDisabled { ft_token_id } => {
+                write!(f, "Token {} is disabled", ft_token_id)
+            }
+            BetError::PendingBetExists => {
+                write!(f, "Previous bet must be resolved before placing a new one")
+            }
+            BetError::BelowMinBet { min_bet } => {
+                write!(f, "minimum bet_size is {} yoctonear", min_bet.0)
+            }
+            BetError::AboveMaxBet { max_bet } => {
+                write!(f, "maximum bet_size is {} yoctonear", max_bet.0)
+            }
+            BetError::InsufficientCredits { credits, bet_size } => write!(
+                f,
+                "Insufficient credits: bet_size is {} yoctonear but only {} yoctonear are available",
+                bet_size.0, credits.0
+            ),
+            BetError::HouseCannotCover { payout, available } => write!(
+                f,
+                "House cannot cover a payout of {} yoctonear, only {} yoctonear are available",
+                payout.0, available.0
+            ),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CreditHolderView {
+    pub account_id: AccountId,
+    pub credits: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingBetView {
+    pub bet_type: bool,
+    pub bet_size: U128,
+    pub commit_block: U64,
+    pub resolvable_from: U64,
+    pub refundable_from: U64,
+    pub ft_token_id: Option<AccountId>,
+}
+
+impl PendingBet {
+    pub fn view(&self) -> PendingBetView {
+        PendingBetView {
+            bet_type: self.bet_type,
+            bet_size: U128(self.bet_size),
+            commit_block: U64(self.commit_block),
+            resolvable_from: U64(self.commit_block + RESOLVE_DELAY_BLOCKS),
+            refundable_from: U64(self.commit_block + BET_TIMEOUT_BLOCKS),
+            ft_token_id: self.ft_token_id.clone(),
+        }
+    }
+
+    //most the contract can owe for this bet, either the win plus fees or a full refund
+    pub fn max_liability(&self) -> u128 {
+        std::cmp::max(self.win_payout + self.nft_cut + self.dev_cut, self.bet_size)
+    }
+}
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct SlotMachine {
     pub owner_id: AccountId,
+    pub nft_id: AccountId,
     pub credits: LookupMap<AccountId, Balance>,
     pub nft_fee: u128, // base 10e-5
     pub dev_fee: u128, // base 10e-5
@@ -28,7 +201,39 @@ pub struct SlotMachine {
     pub max_bet: u128,
     pub min_bet: u128,
     pub min_balance_fraction: u128, //fraction of min_bet that can be held as minimum balance for user
-    pub panic_button: bool,
+    pub paused: PauseFlags,
+    pub pending_bets: LookupMap<AccountId, PendingBet>,
+    pub house_balance: u128,
+    pub total_credits: u128,             // sum of all player credits
+    pub pending_liabilities: u128,       // sum of max_liability of all pending bets
+    pub max_bet_bankroll_fraction: u128, // base 10e-5, 0 disables the dynamic max bet
+    pub nft_mapping_size: u128,          // nft_total_supply of the collection, 0 until read
+    pub nft_reward_per_token: u128,      // nft fees accrued to each token since init
+    pub nft_reward_dust: u128,           // remainder of nft fees not yet split between tokens
+    pub nft_rewards_paid: LookupMap<TokenId, u128>, // nft_reward_per_token at each token's last claim
+    pub nft_epoch: u64, // bumped every time the nft contract is replaced
+    pub nft_round: Option<NftDistributionRound>,
+    pub nft_failed_transfers: UnorderedMap<AccountId, u128>, // nft payouts to be retried
+    pub storage_deposits: LookupMap<AccountId, Balance>,     // NEP-145 registered accounts
+    pub account_storage_usage: u64, // bytes of state paid for by each registered account
+    pub total_storage_deposits: u128, // sum of the storage balances of registered accounts
+    pub ft_assets: UnorderedMap<AccountId, FtAsset>, // whitelisted NEP-141 tokens
+    pub ft_credits: LookupMap<(AccountId, AccountId), Balance>, // (account, token) credits
+    pub ft_nft_rewards_paid: LookupMap<(AccountId, TokenId), u128>, // nft_rewards_paid per token
+    pub min_house_edge: u128,       // base 10e-5, allowed range of the expected house edge
+    pub max_house_edge: u128,       // base 10e-5
+    pub config_delay: u64,          // nanoseconds config proposals wait before taking effect
+    pub config_proposals: UnorderedMap<u64, ConfigProposal>,
+    pub next_config_proposal_id: u64,
+    pub pause_reason: Option<String>,
+    pub roles: UnorderedMap<AccountId, Vec<Role>>, // admin roles granted by the owner
+    pub pending_owner_id: Option<AccountId>,       // proposed owner that has not accepted yet
+    pub staged_upgrade: Option<StagedUpgrade>,     // wasm waiting to be deployed by upgrade
+    pub player_stats: LookupMap<(AccountId, Option<AccountId>), PlayerStats>, // (account, token) stats
+    pub bet_history: LookupMap<AccountId, BetHistory>, // last resolved bets of each account
+    pub game_stats: GameStats,                         // totals of all bets in near
+    pub credit_holders: UnorderedSet<AccountId>,       // accounts with near credits
+    pub v1_migration: Option<V1Migration>, // upgrade from V1 waiting for its credits to be indexed
 }
 
 impl Default for SlotMachine {
@@ -40,45 +245,76 @@ impl Default for SlotMachine {
 #[near_bindgen]
 impl SlotMachine {
     #[init]
-    pub fn new(
-        owner_id: AccountId,
-        nft_fee: U128,
-        dev_fee: U128,
-        house_fee: U128,
-        win_multiplier: U128,
-        max_bet: U128,
-        min_bet: U128,
-        min_balance_fraction: U128,
-    ) -> Self {
+    pub fn new(owner_id: AccountId, nft_id: AccountId, config: ContractConfig) -> Self {
         assert!(
             env::is_valid_account_id(owner_id.as_bytes()),
             "Invalid owner account"
         );
+        assert!(
+            env::is_valid_account_id(nft_id.as_bytes()),
+            "Invalid nft contract account"
+        );
         assert!(!env::state_exists(), "Already initialized");
-        Self {
+        let mut this = Self {
             owner_id,
+            nft_id,
             credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: nft_fee.0,               // 4000
-            dev_fee: dev_fee.0,               // 500
-            house_fee: house_fee.0,           // 500
-            win_multiplier: win_multiplier.0, // 20000
+            nft_fee: 0,
+            dev_fee: 0,
+            house_fee: 0,
+            win_multiplier: 0,
             nft_balance: 0,
             dev_balance: 0,
-            max_bet: max_bet.0,
-            min_bet: min_bet.0,
-            min_balance_fraction: min_balance_fraction.0,
-            panic_button: false,
-        }
+            max_bet: 0,
+            min_bet: 0,
+            min_balance_fraction: 0,
+            paused: PauseFlags::default(),
+            pending_bets: LookupMap::new(b"pending_bets".to_vec()),
+            house_balance: 0,
+            total_credits: 0,
+            pending_liabilities: 0,
+            max_bet_bankroll_fraction: 0,
+            nft_mapping_size: 0,
+            nft_reward_per_token: 0,
+            nft_reward_dust: 0,
+            nft_rewards_paid: LookupMap::new(nft_rewards_paid_prefix(0)),
+            nft_epoch: 0,
+            nft_round: None,
+            nft_failed_transfers: UnorderedMap::new(b"nft_failed_transfers".to_vec()),
+            storage_deposits: LookupMap::new(b"storage_deposits".to_vec()),
+            account_storage_usage: 0,
+            total_storage_deposits: 0,
+            ft_assets: UnorderedMap::new(b"ft_assets".to_vec()),
+            ft_credits: LookupMap::new(b"ft_credits".to_vec()),
+            ft_nft_rewards_paid: LookupMap::new(ft_nft_rewards_paid_prefix(0)),
+            min_house_edge: 0,
+            max_house_edge: FRACTIONAL_BASE,
+            config_delay: timelock::MIN_CONFIG_DELAY,
+            config_proposals: UnorderedMap::new(b"config_proposals".to_vec()),
+            next_config_proposal_id: 0,
+            pause_reason: None,
+            roles: UnorderedMap::new(b"roles".to_vec()),
+            pending_owner_id: None,
+            staged_upgrade: None,
+            player_stats: LookupMap::new(b"player_stats".to_vec()),
+            bet_history: LookupMap::new(b"bet_history".to_vec()),
+            game_stats: GameStats::default(),
+            credit_holders: UnorderedSet::new(b"credit_holders".to_vec()),
+            v1_migration: None,
+        };
+        this.internal_set_config(&config);
+        this.measure_account_storage_usage();
+        migrate::write_state_version();
+        this.internal_sync_nft_supply();
+        this
     }
 
     #[payable]
     pub fn deposit(&mut self) {
-        assert!(
-            !self.panic_button,
-            "Panic mode is on, contract has been paused by owner"
-        );
+        self.assert_not_paused(PausableOperation::Deposits);
         let account_id = env::predecessor_account_id();
         let deposit = env::attached_deposit();
+        self.assert_registered(&account_id);
 
         assert!(
             deposit > (self.min_bet / self.min_balance_fraction),
@@ -86,129 +322,358 @@ impl SlotMachine {
             (self.min_bet / self.min_balance_fraction)
         );
 
-        let mut credits = self.credits.get(&account_id).unwrap_or(0);
-        credits = credits + deposit;
-        self.credits.insert(&account_id, &credits);
+        let credits = self.internal_add_credits(&account_id, None, deposit);
+
+        CoinFlipEvent::Deposit(DepositData {
+            account_id,
+            amount: U128(deposit),
+            credits: U128(credits),
+            ft_token_id: None,
+        })
+        .emit();
     }
-    pub fn retrieve_credits(&mut self) -> Promise {
-        assert!(
-            !self.panic_button,
-            "Panic mode is on, contract has been paused by owner"
-        );
+
+    //withdraws all near credits, or all credits in ft_token_id when given.
+    //Never paused, players can always take their funds out
+    pub fn retrieve_credits(&mut self, ft_token_id: Option<AccountId>) -> Promise {
         let account_id = env::predecessor_account_id();
-        let credits: u128 = self.credits.get(&account_id).unwrap_or(0).into();
-        self.credits.remove(&account_id);
-        Promise::new(env::predecessor_account_id()).transfer(credits)
+        let credits: u128 = self.internal_credits(&account_id, ft_token_id.as_ref());
+        self.internal_remove_credits(&account_id, ft_token_id.as_ref(), credits);
+
+        CoinFlipEvent::Withdrawal(AmountData {
+            account_id: account_id.clone(),
+            amount: U128(credits),
+            ft_token_id: ft_token_id.clone(),
+        })
+        .emit();
+        self.checked_payout(TransferKind::Credits, ft_token_id, account_id, credits)
     }
 
-    pub fn get_credits(&self, account_id: AccountId) -> U128 {
-        self.credits.get(&account_id).unwrap_or(0).into()
+    pub fn get_credits(&self, account_id: AccountId, ft_token_id: Option<AccountId>) -> U128 {
+        U128(self.internal_credits(&account_id, ft_token_id.as_ref()))
+    }
+
+    //accounts holding near credits and their credits, they add up to total_credits
+    pub fn get_credit_holders(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<CreditHolderView> {
+        let limit = std::cmp::min(
+            limit.unwrap_or(MAX_CREDIT_HOLDERS_LIMIT),
+            MAX_CREDIT_HOLDERS_LIMIT,
+        );
+        let holders = self.credit_holders.as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let to_index = std::cmp::min(from_index.saturating_add(limit), holders.len());
+        (from_index..to_index)
+            .filter_map(|index| holders.get(index))
+            .map(|account_id| CreditHolderView {
+                credits: U128(self.internal_credits(&account_id, None)),
+                account_id,
+            })
+            .collect()
+    }
+
+    pub fn get_credit_holders_count(&self) -> U64 {
+        U64(self.credit_holders.len())
     }
 
     //bet_type heads == true, tails == false
-    pub fn play(&mut self, _bet_type: bool, bet_size: U128) -> bool {
+    //locks bet_size credits in a pending bet, outcome is only decided
+    //by resolve_bet in a later block. Bets are in near unless ft_token_id is given
+    pub fn play(
+        &mut self,
+        bet_type: bool,
+        bet_size: U128,
+        ft_token_id: Option<AccountId>,
+    ) -> PendingBetView {
+        let account_id = env::predecessor_account_id();
+        let pending_bet =
+            match self.new_pending_bet(&account_id, bet_type, bet_size.0, ft_token_id.as_ref()) {
+                Ok(pending_bet) => pending_bet,
+                Err(error) => env::panic(error.to_string().as_bytes()),
+            };
+
+        // lock credits until the bet is resolved or refunded
+        self.internal_remove_credits(&account_id, ft_token_id.as_ref(), pending_bet.bet_size);
+        match &ft_token_id {
+            None => self.pending_liabilities += pending_bet.max_liability(),
+            Some(ft_token_id) => {
+                let mut asset = self.internal_ft_asset(ft_token_id);
+                asset.pending_liabilities += pending_bet.max_liability();
+                self.ft_assets.insert(ft_token_id, &asset);
+            }
+        }
+        self.pending_bets.insert(&account_id, &pending_bet);
+
+        CoinFlipEvent::BetPlaced(BetPlacedData {
+            account_id,
+            chosen_side: CoinSide::from(pending_bet.bet_type),
+            bet_size: U128(pending_bet.bet_size),
+            commit_block: U64(pending_bet.commit_block),
+            ft_token_id,
+        })
+        .emit();
+        pending_bet.view()
+    }
+
+    //dry run of play, returns the reason the bet would be rejected or null if it would be accepted
+    pub fn validate_bet(
+        &self,
+        account_id: AccountId,
+        bet_size: U128,
+        ft_token_id: Option<AccountId>,
+    ) -> Option<BetError> {
+        self.new_pending_bet(&account_id, true, bet_size.0, ft_token_id.as_ref())
+            .err()
+    }
+
+    //resolves the pending bet of account_id, can be called by anyone
+    //randomness comes from a block that did not exist when the bet was placed
+    pub fn resolve_bet(&mut self, account_id: AccountId) -> BetResult {
+        let pending_bet = self
+            .pending_bets
+            .get(&account_id)
+            .expect("No pending bet for this account");
+        let current_block = env::block_index();
+        assert!(
+            current_block >= pending_bet.commit_block + RESOLVE_DELAY_BLOCKS,
+            "Bet can only be resolved from block {}",
+            pending_bet.commit_block + RESOLVE_DELAY_BLOCKS
+        );
         assert!(
-            !self.panic_button,
-            "Panic mode is on, contract has been paused by owner"
+            current_block < pending_bet.commit_block + BET_TIMEOUT_BLOCKS,
+            "Bet has timed out and can only be refunded"
         );
+        self.pending_bets.remove(&account_id);
 
-        // check that user has credits
-        let account_id = env::predecessor_account_id();
-        let mut credits = self.credits.get(&account_id).unwrap_or(0);
+        let mut seed: Vec<u8> = env::random_seed();
+        seed.extend_from_slice(account_id.as_bytes());
+        seed.extend_from_slice(&pending_bet.commit_block.to_le_bytes());
+        let rand: u8 = *env::keccak256(&seed).first().unwrap();
 
-        if credits >= bet_size.0 {
-            assert!(
-                bet_size.0 >= self.min_bet,
-                "minimum bet_size is {} yoctonear",
-                self.min_bet
-            );
-            assert!(
-                bet_size.0 <= self.max_bet,
-                "maximum bet_size is {} yoctonear",
-                self.max_bet
-            );
+        let chosen_side = CoinSide::from(pending_bet.bet_type);
+        let landed_side = CoinSide::from(rand < PROB);
+        let won: bool = chosen_side == landed_side;
+        let payout: u128 = if won { pending_bet.win_payout } else { 0 };
 
-            // charge dev and nft fees
-            let mut net_bet: u128 = bet_size.0;
-            let nft_cut: u128 = (&net_bet * self.nft_fee) / FRACTIONAL_BASE;
-            let dev_cut: u128 = (&net_bet * self.dev_fee) / FRACTIONAL_BASE;
-            let house_cut: u128 = (&net_bet * self.house_fee) / FRACTIONAL_BASE;
-
-            net_bet = net_bet - &nft_cut - &dev_cut - &house_cut;
-            self.nft_balance = self.nft_balance + nft_cut;
-            self.dev_balance = self.dev_balance + dev_cut;
-
-            // send off credits
-            credits = credits - bet_size.0;
-
-            let rand: u8 = *env::random_seed().get(0).unwrap();
-            let mut outcome: bool = rand < PROB;
-            if u128::from_be_bytes(
-                env::keccak256(&[(env::used_gas() % 256) as u8, rand])[0..16]
-                    .try_into()
-                    .unwrap(),
-            ) % 2
-                == 0
-            {
-                outcome = !outcome;
+        // charge dev, nft and house fees, then the house takes the net bet and pays out wins.
+        // the solvency checks in play guarantee the tracked bankroll covers wins
+        let net_bet: u128 = pending_bet.bet_size
+            - pending_bet.nft_cut
+            - pending_bet.dev_cut
+            - pending_bet.house_cut;
+        match &pending_bet.ft_token_id {
+            None => {
+                self.pending_liabilities -= pending_bet.max_liability();
+                self.accrue_nft_fees(pending_bet.nft_cut);
+                self.dev_balance += pending_bet.dev_cut;
+                self.house_balance = (self.house_balance + pending_bet.house_cut + net_bet)
+                    .checked_sub(payout)
+                    .expect("House balance cannot cover the payout");
             }
-            if outcome {
-                let won_value = (net_bet * self.win_multiplier) / FRACTIONAL_BASE;
-                credits = credits + won_value;
+            Some(ft_token_id) => {
+                let mut asset = self.internal_ft_asset(ft_token_id);
+                asset.pending_liabilities -= pending_bet.max_liability();
+                asset.accrue_nft_fees(pending_bet.nft_cut, self.nft_mapping_size);
+                asset.dev_balance += pending_bet.dev_cut;
+                asset.house_balance = (asset.house_balance + pending_bet.house_cut + net_bet)
+                    .checked_sub(payout)
+                    .expect("House balance cannot cover the payout");
+                self.ft_assets.insert(ft_token_id, &asset);
             }
+        }
+        CoinFlipEvent::FeesAccrued(FeesData {
+            nft_fee: U128(pending_bet.nft_cut),
+            dev_fee: U128(pending_bet.dev_cut),
+            house_fee: U128(pending_bet.house_cut),
+            ft_token_id: pending_bet.ft_token_id.clone(),
+        })
+        .emit();
 
-            self.credits.insert(&account_id, &credits);
-            outcome
+        let credits = if won {
+            self.internal_add_credits(&account_id, pending_bet.ft_token_id.as_ref(), payout)
         } else {
-            false
-        }
+            self.internal_credits(&account_id, pending_bet.ft_token_id.as_ref())
+        };
+
+        let bet_result = BetResult {
+            account_id,
+            chosen_side,
+            landed_side,
+            won,
+            bet_size: U128(pending_bet.bet_size),
+            nft_fee: U128(pending_bet.nft_cut),
+            dev_fee: U128(pending_bet.dev_cut),
+            house_fee: U128(pending_bet.house_cut),
+            payout: U128(payout),
+            credits: U128(credits),
+            ft_token_id: pending_bet.ft_token_id.clone(),
+        };
+        CoinFlipEvent::BetResolved(&bet_result).emit();
+        self.internal_record_bet(&bet_result.account_id, &pending_bet, won, payout);
+        bet_result
     }
 
-    //retrieve dev funds function
-    #[payable]
-    pub fn retrieve_dev_funds(&mut self) -> Promise {
+    //returns locked credits of a bet that was not resolved in time, can be called by anyone
+    pub fn refund_bet(&mut self, account_id: AccountId) {
+        let pending_bet = self
+            .pending_bets
+            .get(&account_id)
+            .expect("No pending bet for this account");
         assert!(
-            !self.panic_button,
-            "Panic mode is on, contract has been paused by owner"
+            env::block_index() >= pending_bet.commit_block + BET_TIMEOUT_BLOCKS,
+            "Bet can only be refunded from block {}",
+            pending_bet.commit_block + BET_TIMEOUT_BLOCKS
         );
-        assert!(
-            env::predecessor_account_id() == self.owner_id,
-            "Only owner can call this function"
+        self.pending_bets.remove(&account_id);
+        match &pending_bet.ft_token_id {
+            None => self.pending_liabilities -= pending_bet.max_liability(),
+            Some(ft_token_id) => {
+                let mut asset = self.internal_ft_asset(ft_token_id);
+                asset.pending_liabilities -= pending_bet.max_liability();
+                self.ft_assets.insert(ft_token_id, &asset);
+            }
+        }
+        self.internal_add_credits(
+            &account_id,
+            pending_bet.ft_token_id.as_ref(),
+            pending_bet.bet_size,
         );
-        assert_one_yocto();
 
-        let dev_account_id = self.owner_id.clone();
-        let withdrawal_dev_balance = self.dev_balance.clone();
-        self.dev_balance = 0;
+        CoinFlipEvent::BetRefunded(AmountData {
+            account_id,
+            amount: U128(pending_bet.bet_size),
+            ft_token_id: pending_bet.ft_token_id,
+        })
+        .emit();
+    }
+
+    //current max bet, lower than max_bet when capped by the house bankroll
+    pub fn get_max_bet(&self, ft_token_id: Option<AccountId>) -> U128 {
+        match ft_token_id {
+            None => U128(self.effective_max_bet()),
+            Some(ft_token_id) => {
+                U128(self.effective_ft_max_bet(&self.internal_ft_asset(&ft_token_id)))
+            }
+        }
+    }
 
-        Promise::new(dev_account_id).transfer(withdrawal_dev_balance)
+    pub fn get_pending_bet(&self, account_id: AccountId) -> Option<PendingBetView> {
+        self.pending_bets
+            .get(&account_id)
+            .map(|pending_bet| pending_bet.view())
     }
 
-    //adapt to cross contract calls
+    //owner funds the house bankroll used to pay out wins
     #[payable]
-    pub fn retrieve_nft_funds(&mut self, distribution_list: Vec<String>) {
+    pub fn deposit_house_funds(&mut self) {
         assert!(
-            !self.panic_button,
-            "Panic mode is on, contract has been paused by owner"
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
         );
+        self.house_balance += env::attached_deposit();
+
+        CoinFlipEvent::HouseFundsDeposit(AmountData {
+            account_id: self.owner_id.clone(),
+            amount: U128(env::attached_deposit()),
+            ft_token_id: None,
+        })
+        .emit();
+    }
+
+    //owner withdraws from the house bankroll, never below what is owed to players
+    #[payable]
+    pub fn retrieve_house_funds(
+        &mut self,
+        amount: U128,
+        ft_token_id: Option<AccountId>,
+    ) -> Promise {
         assert!(
             env::predecessor_account_id() == self.owner_id,
             "Only owner can call this function"
         );
         assert_one_yocto();
+        match &ft_token_id {
+            None => {
+                assert!(
+                    amount.0 <= self.house_balance,
+                    "House balance is only {} yoctonear",
+                    self.house_balance
+                );
+                assert!(
+                    self.total_liabilities() + amount.0 <= self.available_balance(),
+                    "Withdrawal would leave the contract unable to cover its liabilities"
+                );
+                self.house_balance -= amount.0;
+            }
+            Some(ft_token_id) => {
+                let mut asset = self.internal_ft_asset(ft_token_id);
+                assert!(
+                    amount.0 <= asset.house_balance,
+                    "House balance is only {}",
+                    asset.house_balance
+                );
+                assert!(
+                    asset.total_liabilities() + amount.0 <= asset.balance,
+                    "Withdrawal would leave the contract unable to cover its liabilities"
+                );
+                asset.house_balance -= amount.0;
+                self.ft_assets.insert(ft_token_id, &asset);
+            }
+        }
+
+        CoinFlipEvent::HouseFundsWithdrawal(AmountData {
+            account_id: self.owner_id.clone(),
+            amount,
+            ft_token_id: ft_token_id.clone(),
+        })
+        .emit();
+        self.checked_payout(
+            TransferKind::HouseFunds,
+            ft_token_id,
+            self.owner_id.clone(),
+            amount.0,
+        )
+    }
 
-        let withdrawal_nft_balance = self.nft_balance.clone();
-        self.nft_balance = 0;
-        let collection_size: u128 = distribution_list.len().try_into().unwrap();
-        let piece_nft_balance: u128 = withdrawal_nft_balance / collection_size;
+    //retrieve dev funds function, in near or in ft_token_id when given,
+    //paid to the owner or fee collector calling it
+    #[payable]
+    pub fn retrieve_dev_funds(&mut self, ft_token_id: Option<AccountId>) -> Promise {
+        self.assert_not_paused(PausableOperation::Withdrawals);
+        self.assert_role(Role::FeeCollector);
+        assert_one_yocto();
 
-        for item in distribution_list.into_iter() {
-            Promise::new(item).transfer(piece_nft_balance);
-        }
+        let dev_account_id = env::predecessor_account_id();
+        let withdrawal_dev_balance = match &ft_token_id {
+            None => std::mem::replace(&mut self.dev_balance, 0),
+            Some(ft_token_id) => {
+                let mut asset = self.internal_ft_asset(ft_token_id);
+                let dev_balance = std::mem::replace(&mut asset.dev_balance, 0);
+                self.ft_assets.insert(ft_token_id, &asset);
+                dev_balance
+            }
+        };
+
+        CoinFlipEvent::DevFundsWithdrawal(AmountData {
+            account_id: dev_account_id.clone(),
+            amount: U128(withdrawal_dev_balance),
+            ft_token_id: ft_token_id.clone(),
+        })
+        .emit();
+        self.checked_payout(
+            TransferKind::DevFunds,
+            ft_token_id,
+            dev_account_id,
+            withdrawal_dev_balance,
+        )
     }
 
-    //update contract initialization vars
+    //update contract initialization vars, update_config changes only some of them
     #[payable]
+    #[allow(clippy::too_many_arguments)]
     pub fn update_contract(
         &mut self,
         nft_fee: U128,
@@ -219,25 +684,61 @@ impl SlotMachine {
         min_bet: U128,
         min_balance_fraction: U128,
     ) {
-        assert!(
-            env::predecessor_account_id() == self.owner_id,
-            "Only owner can call this function"
-        );
+        self.assert_role(Role::ConfigManager);
+        assert_one_yocto();
+        self.assert_no_config_delay();
+
+        self.internal_set_config(&ContractConfig {
+            nft_fee,
+            dev_fee,
+            house_fee,
+            win_multiplier,
+            max_bet,
+            min_bet,
+            min_balance_fraction,
+        });
+
+        CoinFlipEvent::config_update(&[
+            ("nft_fee", self.nft_fee.to_string()),
+            ("dev_fee", self.dev_fee.to_string()),
+            ("house_fee", self.house_fee.to_string()),
+            ("win_multiplier", self.win_multiplier.to_string()),
+            ("max_bet", self.max_bet.to_string()),
+            ("min_bet", self.min_bet.to_string()),
+            (
+                "min_balance_fraction",
+                self.min_balance_fraction.to_string(),
+            ),
+        ])
+        .emit();
+    }
+
+    //set the fraction of the house bankroll that can be bet at once, 0 turns it off
+    #[payable]
+    pub fn update_max_bet_bankroll_fraction(&mut self, max_bet_bankroll_fraction: U128) {
+        self.assert_role(Role::ConfigManager);
         assert_one_yocto();
+        self.assert_no_config_delay();
+
+        self.max_bet_bankroll_fraction = max_bet_bankroll_fraction.0;
+        self.assert_valid_config();
 
-        self.nft_fee = nft_fee.0;
-        self.dev_fee = dev_fee.0;
-        self.house_fee = house_fee.0;
-        self.win_multiplier = win_multiplier.0;
-        self.max_bet = max_bet.0;
-        self.min_bet = min_bet.0;
-        self.min_balance_fraction = min_balance_fraction.0;
+        CoinFlipEvent::config_update(&[(
+            "max_bet_bankroll_fraction",
+            self.max_bet_bankroll_fraction.to_string(),
+        )])
+        .emit();
     }
 
-    //return current contract state
+    //return current contract state, get_config returns the settings as a typed struct
     pub fn get_contract_state(&self) -> std::collections::HashMap<String, String> {
         let mut state = std::collections::HashMap::new();
         state.insert(String::from("owner_id"), self.owner_id.to_string());
+        state.insert(String::from("nft_id"), self.nft_id.to_string());
+        state.insert(
+            String::from("nft_mapping_size"),
+            self.nft_mapping_size.to_string(),
+        );
         state.insert(String::from("nft_fee"), self.nft_fee.to_string());
         state.insert(String::from("dev_fee"), self.dev_fee.to_string());
         state.insert(String::from("house_fee"), self.house_fee.to_string());
@@ -247,30 +748,275 @@ impl SlotMachine {
         );
         state.insert(String::from("nft_balance"), self.nft_balance.to_string());
         state.insert(String::from("dev_balance"), self.dev_balance.to_string());
+        state.insert(
+            String::from("house_balance"),
+            self.house_balance.to_string(),
+        );
+        state.insert(
+            String::from("total_credits"),
+            self.total_credits.to_string(),
+        );
+        state.insert(
+            String::from("max_bet_bankroll_fraction"),
+            self.max_bet_bankroll_fraction.to_string(),
+        );
         state.insert(String::from("max_bet"), self.max_bet.to_string());
         state.insert(String::from("min_bet"), self.min_bet.to_string());
         state.insert(
             String::from("min_balance_fraction"),
             self.min_balance_fraction.to_string(),
         );
+        //each token's settings are reported under "<ft_token_id>:<setting>"
+        for (ft_token_id, asset) in self.ft_assets.iter() {
+            let settings = [
+                ("enabled", asset.enabled.to_string()),
+                ("nft_fee", asset.nft_fee.to_string()),
+                ("dev_fee", asset.dev_fee.to_string()),
+                ("house_fee", asset.house_fee.to_string()),
+                ("win_multiplier", asset.win_multiplier.to_string()),
+                ("max_bet", asset.max_bet.to_string()),
+                ("min_bet", asset.min_bet.to_string()),
+                ("house_balance", asset.house_balance.to_string()),
+                ("total_credits", asset.total_credits.to_string()),
+            ];
+            for (setting, value) in settings.iter() {
+                state.insert(format!("{}:{}", ft_token_id, setting), value.clone());
+            }
+        }
         state
     }
+}
 
-    #[payable]
-    pub fn emergency_panic(&mut self, withdrawal_balance: U128) -> Promise {
-        assert!(
-            env::predecessor_account_id() == self.owner_id,
-            "Only owner can call this function"
-        );
-        assert_one_yocto();
+impl SlotMachine {
+    //validates a bet and computes its terms, fees are charged on resolution
+    fn new_pending_bet(
+        &self,
+        account_id: &AccountId,
+        bet_type: bool,
+        bet_size: u128,
+        ft_token_id: Option<&AccountId>,
+    ) -> Result<PendingBet, BetError> {
+        if self.paused.plays {
+            return Err(BetError::ContractPaused {
+                reason: self.pause_reason.clone().unwrap_or_default(),
+            });
+        }
+        let asset = match ft_token_id {
+            None => None,
+            Some(ft_token_id) => match self.ft_assets.get(ft_token_id) {
+                Some(asset) if asset.enabled => Some(asset),
+                Some(_) => {
+                    return Err(BetError::TokenDisabled {
+                        ft_token_id: ft_token_id.clone(),
+                    })
+                }
+                None => {
+                    return Err(BetError::TokenNotAccepted {
+                        ft_token_id: ft_token_id.clone(),
+                    })
+                }
+            },
+        };
+        if self.pending_bets.get(account_id).is_some() {
+            return Err(BetError::PendingBetExists);
+        }
+        let min_bet = asset.as_ref().map_or(self.min_bet, |asset| asset.min_bet);
+        if bet_size < min_bet {
+            return Err(BetError::BelowMinBet {
+                min_bet: U128(min_bet),
+            });
+        }
+        let max_bet = match &asset {
+            None => self.effective_max_bet(),
+            Some(asset) => self.effective_ft_max_bet(asset),
+        };
+        if bet_size > max_bet {
+            return Err(BetError::AboveMaxBet {
+                max_bet: U128(max_bet),
+            });
+        }
+        let credits = self.internal_credits(account_id, ft_token_id);
+        if credits < bet_size {
+            return Err(BetError::InsufficientCredits {
+                credits: U128(credits),
+                bet_size: U128(bet_size),
+            });
+        }
 
-        if self.panic_button {
-            self.panic_button = false;
-        } else {
-            self.panic_button = true;
+        let (nft_fee, dev_fee, house_fee, win_multiplier) = match &asset {
+            None => (
+                self.nft_fee,
+                self.dev_fee,
+                self.house_fee,
+                self.win_multiplier,
+            ),
+            Some(asset) => (
+                asset.nft_fee,
+                asset.dev_fee,
+                asset.house_fee,
+                asset.win_multiplier,
+            ),
+        };
+        let nft_cut: u128 = (bet_size * nft_fee) / FRACTIONAL_BASE;
+        let dev_cut: u128 = (bet_size * dev_fee) / FRACTIONAL_BASE;
+        let house_cut: u128 = (bet_size * house_fee) / FRACTIONAL_BASE;
+        let net_bet: u128 = bet_size - nft_cut - dev_cut - house_cut;
+        let win_payout: u128 = (net_bet * win_multiplier) / FRACTIONAL_BASE;
+
+        let pending_bet = PendingBet {
+            bet_type,
+            bet_size,
+            nft_cut,
+            dev_cut,
+            house_cut,
+            win_payout,
+            commit_block: env::block_index(),
+            ft_token_id: ft_token_id.cloned(),
+        };
+
+        // bet size moves from credits to the pending bet's liability
+        let (current_liabilities, available) = match &asset {
+            None => (
+                self.total_liabilities() - bet_size,
+                self.available_balance(),
+            ),
+            Some(asset) => (asset.total_liabilities() - bet_size, asset.balance),
+        };
+        if current_liabilities + pending_bet.max_liability() > available {
+            return Err(BetError::HouseCannotCover {
+                payout: U128(win_payout),
+                available: U128(available.saturating_sub(current_liabilities)),
+            });
+        }
+        // resolve_bet pays wins out of the tracked bankroll, the part of the payout the
+        // bet itself does not bring in must be covered on top of every bet still pending
+        let house_exposure = win_payout.saturating_sub(house_cut + net_bet);
+        let (house_balance, pending_liabilities) = match &asset {
+            None => (self.house_balance, self.pending_liabilities),
+            Some(asset) => (asset.house_balance, asset.pending_liabilities),
+        };
+        if house_exposure > 0 && pending_liabilities + house_exposure > house_balance {
+            return Err(BetError::HouseCannotCover {
+                payout: U128(win_payout),
+                available: U128(house_balance.saturating_sub(pending_liabilities)),
+            });
+        }
+
+        Ok(pending_bet)
+    }
+
+    //max_bet capped to a fraction of the house bankroll when the dynamic mode is on,
+    //never below min_bet so the game is not locked while the bankroll is refilled
+    fn effective_max_bet(&self) -> u128 {
+        self.bankroll_max_bet(self.max_bet, self.min_bet, self.house_balance)
+    }
+
+    fn effective_ft_max_bet(&self, asset: &FtAsset) -> u128 {
+        self.bankroll_max_bet(asset.max_bet, asset.min_bet, asset.house_balance)
+    }
+
+    fn bankroll_max_bet(&self, max_bet: u128, min_bet: u128, house_balance: u128) -> u128 {
+        if self.max_bet_bankroll_fraction == 0 {
+            return max_bet;
+        }
+        let bankroll_limit = (house_balance * self.max_bet_bankroll_fraction) / FRACTIONAL_BASE;
+        std::cmp::max(std::cmp::min(bankroll_limit, max_bet), min_bet)
+    }
+
+    //credits of account_id in near, or in ft_token_id when given
+    pub(crate) fn internal_credits(
+        &self,
+        account_id: &AccountId,
+        ft_token_id: Option<&AccountId>,
+    ) -> u128 {
+        match ft_token_id {
+            None => self.credits.get(account_id).unwrap_or(0),
+            Some(ft_token_id) => self
+                .ft_credits
+                .get(&(account_id.clone(), ft_token_id.clone()))
+                .unwrap_or(0),
+        }
+    }
+
+    //adds to the credits of account_id and their total, returns the new credits.
+    //Accounts with near credits are kept in credit_holders
+    pub(crate) fn internal_add_credits(
+        &mut self,
+        account_id: &AccountId,
+        ft_token_id: Option<&AccountId>,
+        amount: u128,
+    ) -> u128 {
+        let credits = self.internal_credits(account_id, ft_token_id) + amount;
+        match ft_token_id {
+            None => {
+                self.internal_index_v1_credits(account_id);
+                self.credits.insert(account_id, &credits);
+                if credits > 0 {
+                    self.credit_holders.insert(account_id);
+                }
+                self.total_credits += amount;
+            }
+            Some(ft_token_id) => {
+                self.ft_credits
+                    .insert(&(account_id.clone(), ft_token_id.clone()), &credits);
+                let mut asset = self.internal_ft_asset(ft_token_id);
+                asset.total_credits += amount;
+                self.ft_assets.insert(ft_token_id, &asset);
+            }
         }
+        credits
+    }
+
+    //takes from the credits of account_id and their total, the entry is dropped once empty
+    pub(crate) fn internal_remove_credits(
+        &mut self,
+        account_id: &AccountId,
+        ft_token_id: Option<&AccountId>,
+        amount: u128,
+    ) -> u128 {
+        let credits = self.internal_credits(account_id, ft_token_id) - amount;
+        match ft_token_id {
+            None => {
+                self.internal_index_v1_credits(account_id);
+                if credits == 0 {
+                    self.credits.remove(account_id);
+                    self.credit_holders.remove(account_id);
+                } else {
+                    self.credits.insert(account_id, &credits);
+                }
+                self.total_credits -= amount;
+            }
+            Some(ft_token_id) => {
+                let key = (account_id.clone(), ft_token_id.clone());
+                if credits == 0 {
+                    self.ft_credits.remove(&key);
+                } else {
+                    self.ft_credits.insert(&key, &credits);
+                }
+                let mut asset = self.internal_ft_asset(ft_token_id);
+                asset.total_credits -= amount;
+                self.ft_assets.insert(ft_token_id, &asset);
+            }
+        }
+        credits
+    }
+
+    //everything the contract owes to players, nft holders and the dev
+    fn total_liabilities(&self) -> u128 {
+        self.total_credits + self.pending_liabilities + self.nft_balance + self.dev_balance
+    }
+
+    //contract balance that can be paid out, storage staking and the storage balances
+    //of registered accounts are locked
+    fn available_balance(&self) -> u128 {
+        env::account_balance().saturating_sub(self.locked_storage_balance())
+    }
 
-        Promise::new(self.owner_id.clone()).transfer(withdrawal_balance.0)
+    //cost of the storage in use plus the storage balances of registered accounts.
+    //Bytes accounts already use are counted in both, so the unused part of every
+    //storage balance stays locked however much of it the account fills later
+    fn locked_storage_balance(&self) -> u128 {
+        env::storage_usage() as u128 * env::storage_byte_cost() + self.total_storage_deposits
     }
 }
 
@@ -278,12 +1024,24 @@ impl SlotMachine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{ConfigError, ConfigPatch};
+    use crate::fungible_tokens::{FtAssetConfig, FtTokenConfig};
+    use crate::migrate::{SlotMachineV1, V1MigrationArgs, V1MigrationView};
+    use crate::nft_rewards::NftToken;
+    use crate::pause::PauseFlags;
+    use crate::roles::Role;
+    use crate::stats::{BetHistory, BetRecord, BET_HISTORY_SIZE};
+    use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::json_types::{Base64VecU8, I128};
     use near_sdk::MockedBlockchain;
     use near_sdk::{testing_env, VMContext};
+    use std::convert::TryInto;
 
     const CONTRACT_ACCOUNT: &str = "contract.testnet";
     const SIGNER_ACCOUNT: &str = "signer.testnet";
     const OWNER_ACCOUNT: &str = "owner.testnet";
+    const NFT_ACCOUNT: &str = "nft.testnet";
 
     fn get_context(
         input: Vec<u8>,
@@ -301,7 +1059,7 @@ mod tests {
             block_timestamp: 0,
             account_balance,
             account_locked_balance: 0,
-            storage_usage: 0,
+            storage_usage: current_storage_usage(),
             attached_deposit,
             prepaid_gas: 10u64.pow(18),
             random_seed: vec![0, 1, 2],
@@ -311,27 +1069,95 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_deposit_function() {
-        // set up the mock context into the testing environment
-        const BASE_DEPOSIT: u128 = 10_000_000;
-        let context = get_context(vec![], false, BASE_DEPOSIT.clone(), 0);
-        testing_env!(context);
-        // instantiate a contract variable with the counter at zero
+    //testing_env! keeps the storage written so far but takes its usage from the context,
+    //contexts built after the first one have to carry it over
+    fn current_storage_usage() -> u64 {
+        let blockchain_set =
+            near_sdk::env::BLOCKCHAIN_INTERFACE.with(|interface| interface.borrow().is_some());
+        if blockchain_set {
+            env::storage_usage()
+        } else {
+            0
+        }
+    }
+
+    fn get_contract(
+        owner_id: &str,
+        nft_fee: u128,
+        dev_fee: u128,
+        house_fee: u128,
+        win_multiplier: u128,
+    ) -> SlotMachine {
         let mut contract = SlotMachine {
-            owner_id: OWNER_ACCOUNT.to_string(),
+            owner_id: owner_id.to_string(),
+            nft_id: NFT_ACCOUNT.to_string(),
             credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: 400, // base 10e-5
-            dev_fee: 10,  // base 10e-5
-            house_fee: 10,
-            win_multiplier: 200000, // base 10e-5
+            nft_fee, // base 10e-5
+            dev_fee, // base 10e-5
+            house_fee,
+            win_multiplier, // base 10e-5
             nft_balance: 0,
             dev_balance: 0,
             max_bet: 100_000_000,
             min_bet: 100_000,
             min_balance_fraction: 100,
-            panic_button: false,
+            paused: PauseFlags::default(),
+            pending_bets: LookupMap::new(b"pending_bets".to_vec()),
+            house_balance: 0,
+            total_credits: 0,
+            pending_liabilities: 0,
+            max_bet_bankroll_fraction: 0,
+            nft_mapping_size: 50,
+            nft_reward_per_token: 0,
+            nft_reward_dust: 0,
+            nft_rewards_paid: LookupMap::new(nft_rewards_paid_prefix(0)),
+            nft_epoch: 0,
+            nft_round: None,
+            nft_failed_transfers: UnorderedMap::new(b"nft_failed_transfers".to_vec()),
+            storage_deposits: LookupMap::new(b"storage_deposits".to_vec()),
+            account_storage_usage: 0,
+            total_storage_deposits: 0,
+            ft_assets: UnorderedMap::new(b"ft_assets".to_vec()),
+            ft_credits: LookupMap::new(b"ft_credits".to_vec()),
+            ft_nft_rewards_paid: LookupMap::new(ft_nft_rewards_paid_prefix(0)),
+            min_house_edge: 0,
+            max_house_edge: FRACTIONAL_BASE,
+            // no delay so settings can be changed directly in tests
+            config_delay: 0,
+            config_proposals: UnorderedMap::new(b"config_proposals".to_vec()),
+            next_config_proposal_id: 0,
+            pause_reason: None,
+            roles: UnorderedMap::new(b"roles".to_vec()),
+            pending_owner_id: None,
+            staged_upgrade: None,
+            player_stats: LookupMap::new(b"player_stats".to_vec()),
+            bet_history: LookupMap::new(b"bet_history".to_vec()),
+            game_stats: GameStats::default(),
+            credit_holders: UnorderedSet::new(b"credit_holders".to_vec()),
+            v1_migration: None,
         };
+        contract.measure_account_storage_usage();
+        contract
+            .storage_deposits
+            .insert(&SIGNER_ACCOUNT.to_string(), &0);
+        contract
+    }
+
+    fn insert_credits(contract: &mut SlotMachine, account_id: &str, amount: u128) {
+        let credits = contract.credits.get(&account_id.to_string()).unwrap_or(0);
+        contract.credits.insert(&account_id.to_string(), &amount);
+        contract.credit_holders.insert(&account_id.to_string());
+        contract.total_credits = contract.total_credits + amount - credits;
+    }
+
+    #[test]
+    fn test_deposit_function() {
+        // set up the mock context into the testing environment
+        const BASE_DEPOSIT: u128 = 10_000_000;
+        let context = get_context(vec![], false, BASE_DEPOSIT, 0);
+        testing_env!(context);
+        // instantiate a contract variable with the counter at zero
+        let mut contract = get_contract(OWNER_ACCOUNT, 400, 10, 10, 200000);
         let user_balance1: u128 = contract
             .credits
             .get(&"signer.testnet".to_string())
@@ -352,23 +1178,10 @@ mod tests {
     fn test_deposit_function_minimum() {
         // set up the mock context into the testing environment
         const BASE_DEPOSIT: u128 = 999;
-        let context = get_context(vec![], false, BASE_DEPOSIT.clone(), 0);
+        let context = get_context(vec![], false, BASE_DEPOSIT, 0);
         testing_env!(context);
         // instantiate a contract variable with the counter at zero
-        let mut contract = SlotMachine {
-            owner_id: OWNER_ACCOUNT.to_string(),
-            credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: 400, // base 10e-5
-            dev_fee: 10,  // base 10e-5
-            house_fee: 10,
-            win_multiplier: 200000, // base 10e-5
-            nft_balance: 0,
-            dev_balance: 0,
-            max_bet: 100_000_000,
-            min_bet: 100_000,
-            min_balance_fraction: 100,
-            panic_button: false,
-        };
+        let mut contract = get_contract(OWNER_ACCOUNT, 400, 10, 10, 200000);
         contract.deposit();
     }
 
@@ -378,38 +1191,18 @@ mod tests {
         const BASE_DEPOSIT: u128 = 48_000;
         const CONTRACT_BALANCE: u128 = 1_000_000_000_000_000;
         const WITHDRAWAL_AMOUNT: u128 = 48_000;
-        let context = get_context(
-            vec![],
-            false,
-            BASE_DEPOSIT.clone(),
-            CONTRACT_BALANCE.clone(),
-        );
+        let context = get_context(vec![], false, BASE_DEPOSIT, CONTRACT_BALANCE);
         testing_env!(context);
         // instantiate a contract variable with the counter at zero
-        let mut contract = SlotMachine {
-            owner_id: OWNER_ACCOUNT.to_string(),
-            credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: 400, // base 10e-5
-            dev_fee: 10,  // base 10e-5
-            house_fee: 10,
-            win_multiplier: 200000, // base 10e-5
-            nft_balance: 0,
-            dev_balance: 0,
-            max_bet: 100_000_000,
-            min_bet: 100_000,
-            min_balance_fraction: 100,
-            panic_button: false,
-        };
+        let mut contract = get_contract(OWNER_ACCOUNT, 400, 10, 10, 200000);
 
-        contract
-            .credits
-            .insert(&SIGNER_ACCOUNT.to_string(), &WITHDRAWAL_AMOUNT);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, WITHDRAWAL_AMOUNT);
         let user_balance1: u128 = contract
             .credits
             .get(&"signer.testnet".to_string())
             .unwrap_or(0);
         println!("Value before withdrawal: {}", &user_balance1);
-        contract.retrieve_credits();
+        contract.retrieve_credits(None);
         let user_balance2: u128 = contract
             .credits
             .get(&"signer.testnet".to_string())
@@ -425,34 +1218,14 @@ mod tests {
         // set up the mock context into the testing environment
         const BASE_DEPOSIT: u128 = 0;
         const CONTRACT_BALANCE: u128 = 0;
-        let context = get_context(
-            vec![],
-            false,
-            BASE_DEPOSIT.clone(),
-            CONTRACT_BALANCE.clone(),
-        );
+        let context = get_context(vec![], false, BASE_DEPOSIT, CONTRACT_BALANCE);
         testing_env!(context);
         // instantiate a contract variable with the counter at zero
-        let mut contract = SlotMachine {
-            owner_id: OWNER_ACCOUNT.to_string(),
-            credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: 400, // base 10e-5
-            dev_fee: 10,  // base 10e-5
-            house_fee: 10,
-            win_multiplier: 200000, // base 10e-5
-            nft_balance: 0,
-            dev_balance: 0,
-            max_bet: 100_000_000,
-            min_bet: 100_000,
-            min_balance_fraction: 100,
-            panic_button: false,
-        };
+        let mut contract = get_contract(OWNER_ACCOUNT, 400, 10, 10, 200000);
         const BALANCE_AMOUNT: u128 = 48_000;
-        contract
-            .credits
-            .insert(&SIGNER_ACCOUNT.to_string(), &BALANCE_AMOUNT);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, BALANCE_AMOUNT);
         let user_balance: u128 = contract
-            .get_credits(SIGNER_ACCOUNT.clone().to_string())
+            .get_credits(SIGNER_ACCOUNT.to_string(), None)
             .into();
 
         assert_eq!(BALANCE_AMOUNT, user_balance);
@@ -463,26 +1236,15 @@ mod tests {
         // set up the mock context into the testing environment
         const BASE_DEPOSIT: u128 = 0;
         const CONTRACT_BALANCE: u128 = 0;
-        let context = get_context(vec![], true, BASE_DEPOSIT.clone(), CONTRACT_BALANCE.clone());
+        let context = get_context(vec![], false, BASE_DEPOSIT, CONTRACT_BALANCE);
         testing_env!(context);
         // instantiate a contract variable with the counter at zero
-        let contract = SlotMachine {
-            owner_id: OWNER_ACCOUNT.to_string(),
-            credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: 400, // base 10e-5
-            dev_fee: 10,  // base 10e-5
-            house_fee: 10,
-            win_multiplier: 200000, // base 10e-5
-            nft_balance: 0,
-            dev_balance: 0,
-            max_bet: 100_000_000,
-            min_bet: 100_000,
-            min_balance_fraction: 100,
-            panic_button: false,
-        };
+        let contract = get_contract(OWNER_ACCOUNT, 400, 10, 10, 200000);
+        let context = get_context(vec![], true, BASE_DEPOSIT, CONTRACT_BALANCE);
+        testing_env!(context);
 
         let user_balance: u128 = contract
-            .get_credits(SIGNER_ACCOUNT.clone().to_string())
+            .get_credits(SIGNER_ACCOUNT.to_string(), None)
             .into();
         assert_eq!(0, user_balance);
     }
@@ -494,34 +1256,14 @@ mod tests {
     fn test_play_function() {
         // set up the mock context into the testing environment
         const BASE_DEPOSIT: u128 = 0;
-        const CONTRACT_BALANCE: u128 = 0;
-        let context = get_context(
-            vec![],
-            false,
-            BASE_DEPOSIT.clone(),
-            CONTRACT_BALANCE.clone(),
-        );
+        const CONTRACT_BALANCE: u128 = 1_000_000_000_000_000_000_000_000;
+        let context = get_context(vec![], false, BASE_DEPOSIT, CONTRACT_BALANCE);
         testing_env!(context);
         // instantiate a contract variable with the counter at zero
-        let mut contract = SlotMachine {
-            owner_id: OWNER_ACCOUNT.to_string(),
-            credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: 4000, // base 10e-5
-            dev_fee: 500,  // base 10e-5
-            house_fee: 500,
-            win_multiplier: 20000, // base 10e-5
-            nft_balance: 0,
-            dev_balance: 0,
-            max_bet: 100_000_000,
-            min_bet: 100_000,
-            min_balance_fraction: 100,
-            panic_button: false,
-        };
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 20000);
         println!("Game won: {}", 20000 / FRACTIONAL_BASE);
         const BALANCE_AMOUNT: u128 = 100_000_000;
-        contract
-            .credits
-            .insert(&SIGNER_ACCOUNT.to_string(), &BALANCE_AMOUNT);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, BALANCE_AMOUNT);
 
         const BET_AMOUNT: u128 = 100_000;
 
@@ -529,21 +1271,35 @@ mod tests {
         let mut end_balance: u128;
         let mut game_won: bool;
 
-        let dev_fee: u128 = (&BET_AMOUNT * contract.dev_fee) / FRACTIONAL_BASE;
-        let nft_fee: u128 = (&BET_AMOUNT * contract.nft_fee) / FRACTIONAL_BASE;
-        let house_fee: u128 = (&BET_AMOUNT * contract.house_fee) / FRACTIONAL_BASE;
-        let net_bet: u128 = BET_AMOUNT.clone() - dev_fee - nft_fee - house_fee;
+        let dev_fee: u128 = (BET_AMOUNT * contract.dev_fee) / FRACTIONAL_BASE;
+        let nft_fee: u128 = (BET_AMOUNT * contract.nft_fee) / FRACTIONAL_BASE;
+        let house_fee: u128 = (BET_AMOUNT * contract.house_fee) / FRACTIONAL_BASE;
+        let net_bet: u128 = BET_AMOUNT - dev_fee - nft_fee - house_fee;
         let net_won: u128 = (net_bet * contract.win_multiplier) / FRACTIONAL_BASE;
 
         let total_count: u128 = 30;
         let mut loop_counter: u128 = 0;
         while loop_counter < total_count {
+            let mut context = get_context(vec![], false, BASE_DEPOSIT, CONTRACT_BALANCE);
+            context.block_index = (loop_counter as u64) * RESOLVE_DELAY_BLOCKS;
+            testing_env!(context.clone());
             start_balance = contract
-                .get_credits(SIGNER_ACCOUNT.clone().to_string())
+                .get_credits(SIGNER_ACCOUNT.to_string(), None)
                 .into();
-            game_won = contract.play(true, U128(BET_AMOUNT));
+            contract.play(true, U128(BET_AMOUNT), None);
+
+            context.block_index += RESOLVE_DELAY_BLOCKS;
+            context.storage_usage = env::storage_usage();
+            testing_env!(context);
+            let bet_result = contract.resolve_bet(SIGNER_ACCOUNT.to_string());
+            assert_eq!(bet_result.chosen_side, CoinSide::Heads);
+            assert_eq!(
+                bet_result.won,
+                bet_result.chosen_side == bet_result.landed_side
+            );
+            game_won = bet_result.won;
             end_balance = contract
-                .get_credits(SIGNER_ACCOUNT.clone().to_string())
+                .get_credits(SIGNER_ACCOUNT.to_string(), None)
                 .into();
             if game_won {
                 assert_eq!(
@@ -558,7 +1314,7 @@ mod tests {
                     "user balance doesn't match play result"
                 );
             }
-            loop_counter = loop_counter + 1;
+            loop_counter += 1;
         }
 
         assert_eq!(
@@ -579,35 +1335,15 @@ mod tests {
         // set up the mock context into the testing environment
         const BASE_DEPOSIT: u128 = 0;
         const CONTRACT_BALANCE: u128 = 0;
-        let context = get_context(
-            vec![],
-            false,
-            BASE_DEPOSIT.clone(),
-            CONTRACT_BALANCE.clone(),
-        );
+        let context = get_context(vec![], false, BASE_DEPOSIT, CONTRACT_BALANCE);
         testing_env!(context);
         // instantiate a contract variable with the counter at zero
-        let mut contract = SlotMachine {
-            owner_id: OWNER_ACCOUNT.to_string(),
-            credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: 4000, // base 10e-5
-            dev_fee: 500,  // base 10e-5
-            house_fee: 500,
-            win_multiplier: 20000, // base 10e-5
-            nft_balance: 0,
-            dev_balance: 0,
-            max_bet: 100_000_000,
-            min_bet: 100_000,
-            min_balance_fraction: 100,
-            panic_button: false,
-        };
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 20000);
         const BALANCE_AMOUNT: u128 = 100_000_000;
-        contract
-            .credits
-            .insert(&SIGNER_ACCOUNT.to_string(), &BALANCE_AMOUNT);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, BALANCE_AMOUNT);
 
         const BET_AMOUNT: u128 = 99_999;
-        contract.play(true, U128(BET_AMOUNT));
+        contract.play(true, U128(BET_AMOUNT), None);
     }
 
     #[test]
@@ -616,35 +1352,308 @@ mod tests {
         // set up the mock context into the testing environment
         const BASE_DEPOSIT: u128 = 0;
         const CONTRACT_BALANCE: u128 = 0;
-        let context = get_context(
-            vec![],
-            false,
-            BASE_DEPOSIT.clone(),
-            CONTRACT_BALANCE.clone(),
-        );
+        let context = get_context(vec![], false, BASE_DEPOSIT, CONTRACT_BALANCE);
         testing_env!(context);
         // instantiate a contract variable with the counter at zero
-        let mut contract = SlotMachine {
-            owner_id: OWNER_ACCOUNT.to_string(),
-            credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: 4000, // base 10e-5
-            dev_fee: 500,  // base 10e-5
-            house_fee: 500,
-            win_multiplier: 20000, // base 10e-5
-            nft_balance: 0,
-            dev_balance: 0,
-            max_bet: 100_000_000,
-            min_bet: 100_000,
-            min_balance_fraction: 100,
-            panic_button: false,
-        };
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 20000);
         const BALANCE_AMOUNT: u128 = 100_000_000_000;
-        contract
-            .credits
-            .insert(&SIGNER_ACCOUNT.to_string(), &BALANCE_AMOUNT);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, BALANCE_AMOUNT);
 
         const BET_AMOUNT: u128 = 100_000_001;
-        contract.play(true, U128(BET_AMOUNT));
+        contract.play(true, U128(BET_AMOUNT), None);
+    }
+
+    #[test]
+    fn test_resolve_bet_result() {
+        let mut context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context.clone());
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        const BALANCE_AMOUNT: u128 = 100_000_000;
+        const BET_AMOUNT: u128 = 100_000;
+        insert_credits(&mut contract, SIGNER_ACCOUNT, BALANCE_AMOUNT);
+        contract.house_balance = 10_000_000;
+
+        // bet tails so that the chosen side is not the default one
+        contract.play(false, U128(BET_AMOUNT), None);
+        context.block_index = RESOLVE_DELAY_BLOCKS;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        let bet_result = contract.resolve_bet(SIGNER_ACCOUNT.to_string());
+
+        let nft_fee: u128 = (BET_AMOUNT * 4000) / FRACTIONAL_BASE;
+        let dev_fee: u128 = (BET_AMOUNT * 500) / FRACTIONAL_BASE;
+        let house_fee: u128 = (BET_AMOUNT * 500) / FRACTIONAL_BASE;
+        let net_won: u128 =
+            ((BET_AMOUNT - nft_fee - dev_fee - house_fee) * 200000) / FRACTIONAL_BASE;
+
+        assert_eq!(bet_result.account_id, SIGNER_ACCOUNT.to_string());
+        assert_eq!(bet_result.chosen_side, CoinSide::Tails);
+        assert_eq!(bet_result.won, bet_result.landed_side == CoinSide::Tails);
+        assert_eq!(bet_result.bet_size.0, BET_AMOUNT);
+        assert_eq!(bet_result.nft_fee.0, nft_fee);
+        assert_eq!(bet_result.dev_fee.0, dev_fee);
+        assert_eq!(bet_result.house_fee.0, house_fee);
+        if bet_result.won {
+            assert_eq!(bet_result.payout.0, net_won);
+        } else {
+            assert_eq!(bet_result.payout.0, 0);
+        }
+        assert_eq!(
+            bet_result.credits.0,
+            BALANCE_AMOUNT - BET_AMOUNT + bet_result.payout.0
+        );
+        let credits: u128 = contract
+            .get_credits(SIGNER_ACCOUNT.to_string(), None)
+            .into();
+        assert_eq!(bet_result.credits.0, credits);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bet can only be resolved from block")]
+    fn test_resolve_bet_before_delay() {
+        let context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 20000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 100_000_000);
+
+        contract.play(true, U128(100_000), None);
+        contract.resolve_bet(SIGNER_ACCOUNT.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Previous bet must be resolved before placing a new one")]
+    fn test_play_function_panic_pending_bet() {
+        let context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 20000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 100_000_000);
+
+        contract.play(true, U128(100_000), None);
+        contract.play(true, U128(100_000), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient credits")]
+    fn test_play_function_panic_insufficient_credits() {
+        let context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 20000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 99_999);
+
+        contract.play(true, U128(100_000), None);
+    }
+
+    #[test]
+    fn test_validate_bet() {
+        let context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 20000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000_000);
+
+        // validate_bet is a view call
+        let context = get_context(vec![], true, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+
+        assert_eq!(
+            contract.validate_bet(SIGNER_ACCOUNT.to_string(), U128(100_000), None),
+            None
+        );
+        assert_eq!(
+            contract.validate_bet(SIGNER_ACCOUNT.to_string(), U128(99_999), None),
+            Some(BetError::BelowMinBet {
+                min_bet: U128(100_000)
+            })
+        );
+        assert_eq!(
+            contract.validate_bet(SIGNER_ACCOUNT.to_string(), U128(100_000_001), None),
+            Some(BetError::AboveMaxBet {
+                max_bet: U128(100_000_000)
+            })
+        );
+        assert_eq!(
+            contract.validate_bet(SIGNER_ACCOUNT.to_string(), U128(2_000_000), None),
+            Some(BetError::InsufficientCredits {
+                credits: U128(1_000_000),
+                bet_size: U128(2_000_000)
+            })
+        );
+
+        contract.paused.plays = true;
+        contract.pause_reason = Some(String::from("maintenance"));
+        assert_eq!(
+            contract.validate_bet(SIGNER_ACCOUNT.to_string(), U128(100_000), None),
+            Some(BetError::ContractPaused {
+                reason: String::from("maintenance")
+            })
+        );
+    }
+
+    #[test]
+    fn test_house_balance_accounting() {
+        let mut context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context.clone());
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        const BALANCE_AMOUNT: u128 = 100_000_000;
+        const BET_AMOUNT: u128 = 100_000;
+        const HOUSE_FUNDS: u128 = 10_000_000;
+        insert_credits(&mut contract, SIGNER_ACCOUNT, BALANCE_AMOUNT);
+        contract.house_balance = HOUSE_FUNDS;
+
+        contract.play(true, U128(BET_AMOUNT), None);
+        assert_eq!(contract.total_credits, BALANCE_AMOUNT - BET_AMOUNT);
+        assert!(contract.pending_liabilities > 0);
+
+        context.block_index = RESOLVE_DELAY_BLOCKS;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        let bet_result = contract.resolve_bet(SIGNER_ACCOUNT.to_string());
+
+        let net_bet: u128 =
+            BET_AMOUNT - bet_result.nft_fee.0 - bet_result.dev_fee.0 - bet_result.house_fee.0;
+        if bet_result.won {
+            assert_eq!(
+                contract.house_balance,
+                HOUSE_FUNDS + bet_result.house_fee.0 + net_bet - bet_result.payout.0
+            );
+        } else {
+            assert_eq!(
+                contract.house_balance,
+                HOUSE_FUNDS + bet_result.house_fee.0 + net_bet
+            );
+        }
+        assert_eq!(contract.pending_liabilities, 0);
+        assert_eq!(contract.total_credits, bet_result.credits.0);
+    }
+
+    #[test]
+    fn test_validate_bet_house_cannot_cover() {
+        // contract balance barely above what it owes in credits
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000_000);
+
+        let storage_cost = env::storage_usage() as u128 * env::storage_byte_cost();
+        let mut context = get_context(vec![], true, 0, storage_cost + 1_000_000);
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+
+        match contract.validate_bet(SIGNER_ACCOUNT.to_string(), U128(100_000), None) {
+            Some(BetError::HouseCannotCover { payout, available }) => {
+                assert_eq!(payout.0, (95_000 * 200000) / FRACTIONAL_BASE);
+                assert_eq!(available.0, 100_000);
+            }
+            _ => panic!("bet should not be covered by the house"),
+        }
+    }
+
+    #[test]
+    fn test_validate_bet_bankroll_cannot_cover() {
+        // contract balance covers the win but the tracked bankroll does not
+        let context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000_000);
+        contract.house_balance = 10_000;
+
+        assert_eq!(
+            contract.validate_bet(SIGNER_ACCOUNT.to_string(), U128(100_000), None),
+            Some(BetError::HouseCannotCover {
+                payout: U128((95_000 * 200000) / FRACTIONAL_BASE),
+                available: U128(10_000)
+            })
+        );
+
+        contract.house_balance = 100_000;
+        assert_eq!(
+            contract.validate_bet(SIGNER_ACCOUNT.to_string(), U128(100_000), None),
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Withdrawal would leave the contract unable to cover its liabilities"
+    )]
+    fn test_retrieve_house_funds_liabilities() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000_000);
+        contract.house_balance = 500_000;
+
+        // contract only holds the player credits, house funds were never deposited
+        let storage_cost = env::storage_usage() as u128 * env::storage_byte_cost();
+        let mut context = get_context(vec![], false, 1, storage_cost + 1_000_000);
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+
+        contract.retrieve_house_funds(U128(500_000), None);
+    }
+
+    #[test]
+    fn test_get_max_bet_bankroll_fraction() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+        assert_eq!(contract.get_max_bet(None).0, 100_000_000);
+
+        // 1% of the bankroll
+        contract.update_max_bet_bankroll_fraction(U128(1_000));
+        contract.house_balance = 5_000_000_000;
+        assert_eq!(contract.get_max_bet(None).0, 50_000_000);
+
+        // bounded by the static limits
+        contract.house_balance = 50_000_000_000;
+        assert_eq!(contract.get_max_bet(None).0, 100_000_000);
+        contract.house_balance = 1_000;
+        assert_eq!(contract.get_max_bet(None).0, 100_000);
+
+        contract.update_max_bet_bankroll_fraction(U128(0));
+        assert_eq!(contract.get_max_bet(None).0, 100_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "maximum bet_size is 50000000 yoctonear")]
+    fn test_play_function_panic_dynamic_max_bet() {
+        let context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 100_000_000);
+        contract.max_bet_bankroll_fraction = 1_000;
+        contract.house_balance = 5_000_000_000;
+
+        contract.play(true, U128(50_000_001), None);
+    }
+
+    #[test]
+    fn test_refund_bet_after_timeout() {
+        let mut context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context.clone());
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 20000);
+        const BALANCE_AMOUNT: u128 = 100_000_000;
+        insert_credits(&mut contract, SIGNER_ACCOUNT, BALANCE_AMOUNT);
+
+        contract.play(true, U128(100_000), None);
+        let locked_balance: u128 = contract
+            .get_credits(SIGNER_ACCOUNT.to_string(), None)
+            .into();
+        assert_eq!(locked_balance, BALANCE_AMOUNT - 100_000);
+
+        context.block_index = BET_TIMEOUT_BLOCKS;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        contract.refund_bet(SIGNER_ACCOUNT.to_string());
+
+        let final_balance: u128 = contract
+            .get_credits(SIGNER_ACCOUNT.to_string(), None)
+            .into();
+        assert_eq!(final_balance, BALANCE_AMOUNT);
+        assert!(contract
+            .get_pending_bet(SIGNER_ACCOUNT.to_string())
+            .is_none());
+        assert_eq!(contract.nft_balance, 0);
+        assert_eq!(contract.dev_balance, 0);
     }
 
     // update contract
@@ -656,28 +1665,10 @@ mod tests {
         // set up the mock context into the testing environment
         const BASE_DEPOSIT: u128 = 0;
         const CONTRACT_BALANCE: u128 = 0;
-        let context = get_context(
-            vec![],
-            false,
-            BASE_DEPOSIT.clone(),
-            CONTRACT_BALANCE.clone(),
-        );
+        let context = get_context(vec![], false, BASE_DEPOSIT, CONTRACT_BALANCE);
         testing_env!(context);
         // instantiate a contract variable with the counter at zero
-        let mut contract = SlotMachine {
-            owner_id: OWNER_ACCOUNT.to_string(),
-            credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: 4000, // base 10e-5
-            dev_fee: 500,  // base 10e-5
-            house_fee: 500,
-            win_multiplier: 20000, // base 10e-5
-            nft_balance: 0,
-            dev_balance: 0,
-            max_bet: 100_000_000,
-            min_bet: 100_000,
-            min_balance_fraction: 100,
-            panic_button: false,
-        };
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 20000);
 
         contract.update_contract(
             U128(10),
@@ -695,44 +1686,26 @@ mod tests {
         // set up the mock context into the testing environment
         const BASE_DEPOSIT: u128 = 1;
         const CONTRACT_BALANCE: u128 = 0;
-        let context = get_context(
-            vec![],
-            false,
-            BASE_DEPOSIT.clone(),
-            CONTRACT_BALANCE.clone(),
-        );
+        let context = get_context(vec![], false, BASE_DEPOSIT, CONTRACT_BALANCE);
         testing_env!(context);
         // instantiate a contract variable with the counter at zero
-        let mut contract = SlotMachine {
-            owner_id: SIGNER_ACCOUNT.to_string(),
-            credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: 4000, // base 10e-5
-            dev_fee: 500,  // base 10e-5
-            house_fee: 500,
-            win_multiplier: 20000, // base 10e-5
-            nft_balance: 0,
-            dev_balance: 0,
-            max_bet: 100_000_000,
-            min_bet: 100_000,
-            min_balance_fraction: 100,
-            panic_button: false,
-        };
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 20000);
 
         contract.update_contract(
             U128(10),
             U128(11),
             U128(12),
-            U128(13),
-            U128(15),
+            U128(190000),
             U128(16),
+            U128(15),
             U128(17),
         );
         assert_eq!(contract.nft_fee, 10, "nft_fee");
         assert_eq!(contract.dev_fee, 11, "dev_fee");
         assert_eq!(contract.house_fee, 12, "house_fee");
-        assert_eq!(contract.win_multiplier, 13, "win_multiplier");
-        assert_eq!(contract.max_bet, 15, "max_bet");
-        assert_eq!(contract.min_bet, 16, "min_bet");
+        assert_eq!(contract.win_multiplier, 190000, "win_multiplier");
+        assert_eq!(contract.max_bet, 16, "max_bet");
+        assert_eq!(contract.min_bet, 15, "min_bet");
         assert_eq!(contract.min_balance_fraction, 17, "min_balance_fraction");
     }
 
@@ -741,28 +1714,10 @@ mod tests {
         // set up the mock context into the testing environment
         const BASE_DEPOSIT: u128 = 0;
         const CONTRACT_BALANCE: u128 = 0;
-        let context = get_context(
-            vec![],
-            false,
-            BASE_DEPOSIT.clone(),
-            CONTRACT_BALANCE.clone(),
-        );
+        let context = get_context(vec![], false, BASE_DEPOSIT, CONTRACT_BALANCE);
         testing_env!(context);
         // instantiate a contract variable with the counter at zero
-        let contract = SlotMachine {
-            owner_id: OWNER_ACCOUNT.to_string(),
-            credits: LookupMap::new(b"credits".to_vec()),
-            nft_fee: 400, // base 10e-5
-            dev_fee: 10,  // base 10e-5
-            house_fee: 10,
-            win_multiplier: 200000, // base 10e-5
-            nft_balance: 0,
-            dev_balance: 0,
-            max_bet: 100_000_000,
-            min_bet: 100_000,
-            min_balance_fraction: 100,
-            panic_button: false,
-        };
+        let contract = get_contract(OWNER_ACCOUNT, 400, 10, 10, 200000);
 
         let contract_copy: std::collections::HashMap<String, String> =
             contract.get_contract_state();
@@ -771,6 +1726,10 @@ mod tests {
             contract_copy.get("owner_id").unwrap().clone(),
             contract.owner_id.to_string()
         );
+        assert_eq!(
+            contract_copy.get("nft_id").unwrap().clone(),
+            contract.nft_id.to_string()
+        );
 
         assert_eq!(
             contract_copy.get("nft_fee").unwrap().clone(),
@@ -810,5 +1769,2214 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_accrue_nft_fees() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+
+        contract.accrue_nft_fees(1_020);
+        assert_eq!(contract.nft_balance, 1_020);
+        assert_eq!(contract.nft_reward_per_token, 20);
+        assert_eq!(contract.nft_reward_dust, 20);
+        assert_eq!(contract.get_nft_claimable("1".to_string(), None).0, 20);
+
+        //dust is carried over to the next accrual
+        contract.accrue_nft_fees(30);
+        assert_eq!(contract.nft_reward_per_token, 21);
+        assert_eq!(contract.nft_reward_dust, 0);
+        assert_eq!(contract.get_nft_claimable("1".to_string(), None).0, 21);
+    }
+
+    #[test]
+    fn test_claim_nft_rewards_internal() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+
+        contract.accrue_nft_fees(5_000);
+        assert_eq!(contract.internal_claim_nft_rewards(&"1".to_string()), 100);
+        assert_eq!(contract.nft_balance, 4_900);
+        assert_eq!(contract.get_nft_claimable("1".to_string(), None).0, 0);
+        assert_eq!(contract.get_nft_claimable("2".to_string(), None).0, 100);
+
+        //claiming again only pays what accrued since the last claim
+        contract.accrue_nft_fees(5_000);
+        assert_eq!(contract.internal_claim_nft_rewards(&"1".to_string()), 100);
+        assert_eq!(contract.internal_claim_nft_rewards(&"2".to_string()), 200);
+        assert_eq!(contract.nft_balance, 9_600);
+    }
+
+    #[test]
+    fn test_update_nft_contract() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+
+        contract.accrue_nft_fees(5_000);
+        assert_eq!(contract.internal_claim_nft_rewards(&"1".to_string()), 100);
+
+        //unclaimed 4_900 rolls over to the 10 tokens of the new collection once its supply is read
+        contract.update_nft_contract("new_nft.testnet".to_string());
+        let nft_contract = contract.get_nft_contract();
+        assert_eq!(nft_contract.nft_id, "new_nft.testnet".to_string());
+        assert_eq!(nft_contract.nft_mapping_size.0, 0);
+        assert_eq!(nft_contract.nft_epoch.0, 1);
+        assert_eq!(contract.nft_balance, 4_900);
+        assert_eq!(contract.get_nft_claimable("1".to_string(), None).0, 0);
+
+        nft_supply_callback(&mut contract, 10);
+        assert_eq!(contract.get_nft_contract().nft_mapping_size.0, 10);
+        assert_eq!(contract.get_nft_claimable("1".to_string(), None).0, 490);
+        assert_eq!(contract.get_nft_claimable("2".to_string(), None).0, 490);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can call this function")]
+    fn test_update_nft_contract_assert_panic_no_owner() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_nft_contract("new_nft.testnet".to_string());
+    }
+
+    //nft contract answers nft_total_supply with total_supply
+    fn nft_supply_callback_context(total_supply: u128) {
+        let mut context = get_context(vec![], false, 0, 0);
+        context.predecessor_account_id = CONTRACT_ACCOUNT.to_string();
+        testing_env!(
+            context,
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Successful(
+                near_sdk::serde_json::to_vec(&U128(total_supply)).unwrap()
+            )]
+        );
+    }
+
+    fn nft_supply_callback(contract: &mut SlotMachine, total_supply: u128) {
+        nft_supply_callback_context(total_supply);
+        let nft_id = contract.nft_id.clone();
+        assert_eq!(contract.on_nft_total_supply(nft_id).0, total_supply);
+    }
+
+    #[test]
+    fn test_nft_supply_splits_fees_accrued_without_tokens() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 5_000_000);
+        nft_supply_callback(&mut contract, 0);
+
+        //fees are carried over while the collection has no tokens
+        contract.accrue_nft_fees(1_000);
+        let mut asset = contract.internal_ft_asset(&FT_ACCOUNT.to_string());
+        asset.accrue_nft_fees(500, contract.nft_mapping_size);
+        contract.ft_assets.insert(&FT_ACCOUNT.to_string(), &asset);
+        assert_eq!(contract.get_nft_claimable("1".to_string(), None).0, 0);
+
+        nft_supply_callback(&mut contract, 4);
+        assert_eq!(contract.get_nft_claimable("1".to_string(), None).0, 250);
+        assert_eq!(
+            contract
+                .get_nft_claimable("1".to_string(), Some(FT_ACCOUNT.to_string()))
+                .0,
+            125
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "nft contract was replaced while the call was in flight")]
+    fn test_nft_supply_panic_replaced_contract() {
+        testing_env!(get_context(vec![], false, 1, 0));
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+        let old_nft_id = contract.nft_id.clone();
+        contract.update_nft_contract("new_nft.testnet".to_string());
+        nft_supply_callback_context(10);
+        contract.on_nft_total_supply(old_nft_id);
+    }
+
+    fn nft_tokens(token_ids: &[&str]) -> Vec<NftToken> {
+        token_ids
+            .iter()
+            .map(|token_id| NftToken {
+                token_id: token_id.to_string(),
+                owner_id: format!("holder{}.testnet", token_id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_nft_distribution_round() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+
+        contract.accrue_nft_fees(5_000);
+        contract.internal_claim_nft_rewards(&"1".to_string());
+        contract.start_nft_distribution();
+
+        let payouts = contract.internal_distribute_nft_chunk(&nft_tokens(&["1", "2", "3"]), 0, 3);
+        assert_eq!(payouts, vec![0, 100, 100]);
+        let round = contract.get_nft_distribution().unwrap();
+        assert_eq!(round.next_index.0, 3);
+        assert_eq!(round.processed.0, 3);
+        assert_eq!(round.total_paid.0, 200);
+        assert!(!round.finished);
+
+        //fees accrued after the snapshot are left for the next round
+        contract.accrue_nft_fees(5_000);
+        let payouts = contract.internal_distribute_nft_chunk(&nft_tokens(&["4"]), 3, 3);
+        assert_eq!(payouts, vec![100]);
+        let round = contract.get_nft_distribution().unwrap();
+        assert_eq!(round.next_index.0, 4);
+        assert_eq!(round.total_paid.0, 300);
+        assert!(round.finished);
+        assert_eq!(contract.get_nft_claimable("4".to_string(), None).0, 100);
+        assert_eq!(contract.get_nft_claimable("5".to_string(), None).0, 200);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Previous nft distribution round must be finished before starting a new one"
+    )]
+    fn test_start_nft_distribution_panic_round_in_progress() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.start_nft_distribution();
+        contract.start_nft_distribution();
+    }
+
+    #[test]
+    fn test_failed_nft_transfer() {
+        //contract holds the nft rewards it sends out again
+        let context = get_context(vec![], false, 1, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+
+        contract.accrue_nft_fees(5_000);
+        let amount = contract.internal_claim_nft_rewards(&"1".to_string());
+        contract.internal_record_failed_nft_transfer("holder1.testnet".to_string(), amount);
+        assert_eq!(contract.nft_balance, 5_000);
+        let failed = contract.get_nft_failed_transfers(None, None);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].account_id, "holder1.testnet".to_string());
+        assert_eq!(failed[0].amount.0, 100);
+
+        contract.retry_nft_transfer("holder1.testnet".to_string());
+        assert_eq!(contract.nft_balance, 4_900);
+        assert_eq!(contract.get_nft_failed_transfers(None, None).len(), 0);
+    }
+
+    #[test]
+    fn test_restore_failed_transfer() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000);
+
+        contract.internal_restore_transfer(TransferKind::Credits, SIGNER_ACCOUNT.to_string(), 500);
+        assert_eq!(
+            contract.get_credits(SIGNER_ACCOUNT.to_string(), None).0,
+            1_500
+        );
+        assert_eq!(contract.total_credits, 1_500);
+
+        contract.internal_restore_transfer(TransferKind::DevFunds, OWNER_ACCOUNT.to_string(), 200);
+        assert_eq!(contract.dev_balance, 200);
+
+        contract.internal_restore_transfer(
+            TransferKind::HouseFunds,
+            OWNER_ACCOUNT.to_string(),
+            300,
+        );
+        assert_eq!(contract.house_balance, 300);
+
+        contract.internal_restore_transfer(
+            TransferKind::NftRewards,
+            SIGNER_ACCOUNT.to_string(),
+            100,
+        );
+        assert_eq!(contract.nft_balance, 100);
+        assert_eq!(contract.get_nft_failed_transfers(None, None).len(), 1);
+
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(logs.len(), 4);
+        assert_eq!(
+            logs[0],
+            r#"EVENT_JSON:{"standard":"coinflip","version":"1.0.0","event":"transfer_failed","data":{"kind":"credits","account_id":"signer.testnet","amount":"500"}}"#
+        );
+    }
+
+    fn get_events() -> Vec<near_sdk::serde_json::Value> {
+        near_sdk::test_utils::get_logs()
+            .iter()
+            .map(|log| {
+                assert!(log.starts_with("EVENT_JSON:"), "not an event: {}", log);
+                near_sdk::serde_json::from_str(&log["EVENT_JSON:".len()..]).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_events() {
+        let mut context = get_context(vec![], false, 10_000_000, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context.clone());
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.house_balance = 10_000_000;
+
+        contract.deposit();
+        contract.play(true, U128(100_000), None);
+        let events = get_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["standard"], "coinflip");
+        assert_eq!(events[0]["version"], "1.0.0");
+        assert_eq!(events[0]["event"], "deposit");
+        assert_eq!(events[0]["data"]["account_id"], SIGNER_ACCOUNT);
+        assert_eq!(events[0]["data"]["amount"], "10000000");
+        assert_eq!(events[1]["event"], "bet_placed");
+        assert_eq!(events[1]["data"]["chosen_side"], "heads");
+        assert_eq!(events[1]["data"]["bet_size"], "100000");
+
+        context.block_index = RESOLVE_DELAY_BLOCKS;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        let bet_result = contract.resolve_bet(SIGNER_ACCOUNT.to_string());
+        let events = get_events();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0]["event"], "fees_accrued");
+        assert_eq!(
+            events[0]["data"]["nft_fee"],
+            bet_result.nft_fee.0.to_string()
+        );
+        assert_eq!(events[1]["event"], "bet_resolved");
+        assert_eq!(events[1]["data"]["won"], bet_result.won);
+        assert_eq!(
+            events[1]["data"]["credits"],
+            bet_result.credits.0.to_string()
+        );
+        assert_eq!(events[2]["event"], "game_stats_update");
+        assert_eq!(events[2]["data"]["total_bets"], "1");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Account other.testnet is not registered, call storage_deposit first"
+    )]
+    fn test_deposit_function_panic_not_registered() {
+        let mut context = get_context(vec![], false, 10_000_000, 0);
+        context.predecessor_account_id = "other.testnet".to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 400, 10, 10, 200000);
+        contract.deposit();
+    }
+
+    #[test]
+    fn test_storage_deposit() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context.clone());
+        let mut contract = get_contract(OWNER_ACCOUNT, 400, 10, 10, 200000);
+        let min_balance = contract.storage_balance_bounds().min.0;
+        assert!(min_balance > 0);
+        assert_eq!(
+            contract.storage_balance_bounds().max.unwrap().0,
+            min_balance
+        );
+
+        let mut context = get_context(vec![], false, min_balance + 100, 0);
+        context.predecessor_account_id = "other.testnet".to_string();
+        testing_env!(context);
+        let storage_balance = contract.storage_deposit(None, None);
+        assert_eq!(storage_balance.total.0, min_balance);
+        assert_eq!(storage_balance.available.0, 0);
+        assert_eq!(
+            contract
+                .storage_balance_of("other.testnet".try_into().unwrap())
+                .unwrap()
+                .total
+                .0,
+            min_balance
+        );
+        contract.deposit();
+        assert_eq!(
+            contract.get_credits("other.testnet".to_string(), None).0,
+            min_balance + 100
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The attached deposit is less than the minimum storage balance")]
+    fn test_storage_deposit_panic_below_minimum() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 400, 10, 10, 200000);
+        contract.storage_deposit(Some("other.testnet".try_into().unwrap()), None);
+    }
+
+    #[test]
+    fn test_storage_unregister() {
+        let context = get_context(vec![], false, 1, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 400, 10, 10, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000);
+
+        assert!(contract.storage_unregister(Some(true)));
+        assert_eq!(contract.get_credits(SIGNER_ACCOUNT.to_string(), None).0, 0);
+        assert_eq!(contract.total_credits, 0);
+        assert!(contract
+            .storage_balance_of(SIGNER_ACCOUNT.try_into().unwrap())
+            .is_none());
+        assert!(!contract.storage_unregister(None));
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't unregister an account with credits without force")]
+    fn test_storage_unregister_panic_credits_without_force() {
+        let context = get_context(vec![], false, 1, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 400, 10, 10, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000);
+
+        contract.storage_unregister(None);
+    }
+
+    const FT_ACCOUNT: &str = "usdc.testnet";
+
+    fn ft_config(nft_fee: u128, min_bet: u128, max_bet: u128) -> FtAssetConfig {
+        FtAssetConfig {
+            nft_fee: U128(nft_fee),
+            dev_fee: U128(500),
+            house_fee: U128(500),
+            win_multiplier: U128(200000),
+            max_bet: U128(max_bet),
+            min_bet: U128(min_bet),
+        }
+    }
+
+    //whitelists FT_ACCOUNT and funds its house bankroll
+    fn add_ft_token(contract: &mut SlotMachine, house_funds: u128) {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context.clone());
+        contract.add_ft_token(FT_ACCOUNT.to_string(), ft_config(4000, 1_000, 1_000_000));
+
+        context.predecessor_account_id = FT_ACCOUNT.to_string();
+        context.attached_deposit = 0;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        contract.ft_on_transfer(
+            OWNER_ACCOUNT.try_into().unwrap(),
+            U128(house_funds),
+            fungible_tokens::HOUSE_FUNDS_MSG.to_string(),
+        );
+    }
+
+    fn ft_deposit(contract: &mut SlotMachine, account_id: &str, amount: u128) {
+        let mut context = get_context(vec![], false, 0, 0);
+        context.predecessor_account_id = FT_ACCOUNT.to_string();
+        testing_env!(context);
+        contract.ft_on_transfer(account_id.try_into().unwrap(), U128(amount), String::new());
+    }
+
+    #[test]
+    fn test_ft_on_transfer() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 5_000_000);
+        ft_deposit(&mut contract, SIGNER_ACCOUNT, 200_000);
+
+        let ft_token_id = Some(FT_ACCOUNT.to_string());
+        assert_eq!(
+            contract
+                .get_credits(SIGNER_ACCOUNT.to_string(), ft_token_id)
+                .0,
+            200_000
+        );
+        assert_eq!(contract.get_credits(SIGNER_ACCOUNT.to_string(), None).0, 0);
+        let assets = contract.get_ft_tokens();
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].ft_token_id, FT_ACCOUNT);
+        assert_eq!(assets[0].balance.0, 5_200_000);
+        assert_eq!(assets[0].house_balance.0, 5_000_000);
+        assert_eq!(assets[0].total_credits.0, 200_000);
+        assert_eq!(
+            get_events()[0]["data"]["ft_token_id"].as_str(),
+            Some(FT_ACCOUNT)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Token dai.testnet is not accepted")]
+    fn test_ft_on_transfer_panic_not_accepted() {
+        let mut context = get_context(vec![], false, 0, 0);
+        context.predecessor_account_id = "dai.testnet".to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.ft_on_transfer(
+            SIGNER_ACCOUNT.try_into().unwrap(),
+            U128(200_000),
+            String::new(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can fund the house bankroll")]
+    fn test_ft_on_transfer_panic_house_funds_no_owner() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 0);
+        let mut context = get_context(vec![], false, 0, 0);
+        context.predecessor_account_id = FT_ACCOUNT.to_string();
+        testing_env!(context);
+        contract.ft_on_transfer(
+            SIGNER_ACCOUNT.try_into().unwrap(),
+            U128(200_000),
+            fungible_tokens::HOUSE_FUNDS_MSG.to_string(),
+        );
+    }
+
+    #[test]
+    fn test_ft_play_and_resolve() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 5_000_000);
+        ft_deposit(&mut contract, SIGNER_ACCOUNT, 200_000);
+        let ft_token_id = Some(FT_ACCOUNT.to_string());
+
+        //limits and solvency of the token apply, not the near ones
+        assert_eq!(
+            contract.validate_bet(SIGNER_ACCOUNT.to_string(), U128(100), ft_token_id.clone()),
+            Some(BetError::BelowMinBet {
+                min_bet: U128(1_000)
+            })
+        );
+        assert_eq!(
+            contract.validate_bet(
+                SIGNER_ACCOUNT.to_string(),
+                U128(100_000),
+                Some("dai.testnet".to_string())
+            ),
+            Some(BetError::TokenNotAccepted {
+                ft_token_id: "dai.testnet".to_string()
+            })
+        );
+
+        let mut context = get_context(vec![], false, 0, 0);
+        testing_env!(context.clone());
+        let pending_bet = contract.play(true, U128(100_000), ft_token_id.clone());
+        assert_eq!(pending_bet.ft_token_id, ft_token_id);
+        assert_eq!(
+            contract
+                .get_credits(SIGNER_ACCOUNT.to_string(), ft_token_id.clone())
+                .0,
+            100_000
+        );
+
+        context.block_index = RESOLVE_DELAY_BLOCKS;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        let bet_result = contract.resolve_bet(SIGNER_ACCOUNT.to_string());
+        assert_eq!(bet_result.ft_token_id, ft_token_id);
+        assert_eq!(
+            contract
+                .get_credits(SIGNER_ACCOUNT.to_string(), ft_token_id.clone())
+                .0,
+            bet_result.credits.0
+        );
+
+        //near ledger is untouched and every token received is accounted for
+        assert_eq!(contract.total_credits, 0);
+        assert_eq!(contract.nft_balance, 0);
+        let asset = contract.internal_ft_asset(&FT_ACCOUNT.to_string());
+        assert_eq!(asset.nft_balance, 4_000);
+        assert_eq!(asset.dev_balance, 500);
+        assert_eq!(asset.pending_liabilities, 0);
+        assert_eq!(
+            asset.balance,
+            asset.total_credits + asset.house_balance + asset.dev_balance + asset.nft_balance
+        );
+        assert_eq!(
+            contract
+                .get_nft_claimable("1".to_string(), ft_token_id.clone())
+                .0,
+            4_000 / 50
+        );
+    }
+
+    #[test]
+    fn test_ft_retrieve_credits() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 0);
+        ft_deposit(&mut contract, SIGNER_ACCOUNT, 200_000);
+        let ft_token_id = Some(FT_ACCOUNT.to_string());
+
+        // ft_transfer is sent with 1 yoctonear attached
+        let context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+        contract.retrieve_credits(ft_token_id.clone());
+        assert_eq!(
+            contract
+                .get_credits(SIGNER_ACCOUNT.to_string(), ft_token_id.clone())
+                .0,
+            0
+        );
+        assert_eq!(contract.get_ft_tokens()[0].balance.0, 0);
+
+        //a failed ft_transfer gives the credits back
+        contract.internal_restore_ft_transfer(
+            TransferKind::Credits,
+            FT_ACCOUNT.to_string(),
+            SIGNER_ACCOUNT.to_string(),
+            200_000,
+        );
+        assert_eq!(
+            contract
+                .get_credits(SIGNER_ACCOUNT.to_string(), ft_token_id)
+                .0,
+            200_000
+        );
+        let assets = contract.get_ft_tokens();
+        assert_eq!(assets[0].balance.0, 200_000);
+        assert_eq!(assets[0].total_credits.0, 200_000);
+    }
+
+    #[test]
+    fn test_update_ft_token() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 5_000_000);
+        ft_deposit(&mut contract, SIGNER_ACCOUNT, 200_000);
+
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        contract.update_ft_token(FT_ACCOUNT.to_string(), ft_config(2000, 10_000, 50_000));
+
+        //bets in the token use its own fees and limits
+        let ft_token_id = Some(FT_ACCOUNT.to_string());
+        assert_eq!(
+            contract.validate_bet(
+                SIGNER_ACCOUNT.to_string(),
+                U128(60_000),
+                ft_token_id.clone()
+            ),
+            Some(BetError::AboveMaxBet {
+                max_bet: U128(50_000)
+            })
+        );
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        contract.play(true, U128(50_000), ft_token_id.clone());
+        let pending_bet = contract
+            .pending_bets
+            .get(&SIGNER_ACCOUNT.to_string())
+            .unwrap();
+        assert_eq!(pending_bet.nft_cut, 1_000);
+        assert_eq!(pending_bet.dev_cut, 250);
+
+        let state = contract.get_contract_state();
+        assert_eq!(state["usdc.testnet:nft_fee"], "2000");
+        assert_eq!(state["usdc.testnet:max_bet"], "50000");
+        assert_eq!(state["usdc.testnet:enabled"], "true");
+        //near settings are unchanged
+        assert_eq!(state["nft_fee"], "4000");
+    }
+
+    #[test]
+    fn test_disable_ft_token() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 5_000_000);
+        ft_deposit(&mut contract, SIGNER_ACCOUNT, 200_000);
+
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        contract.disable_ft_token(FT_ACCOUNT.to_string());
+
+        let ft_token_id = Some(FT_ACCOUNT.to_string());
+        assert!(
+            !contract
+                .get_ft_token(FT_ACCOUNT.to_string())
+                .unwrap()
+                .enabled
+        );
+        assert_eq!(
+            contract.validate_bet(
+                SIGNER_ACCOUNT.to_string(),
+                U128(10_000),
+                ft_token_id.clone()
+            ),
+            Some(BetError::TokenDisabled {
+                ft_token_id: FT_ACCOUNT.to_string()
+            })
+        );
+        assert_eq!(
+            contract.get_contract_state()["usdc.testnet:enabled"],
+            "false"
+        );
+
+        //credits can still be retrieved
+        let context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context);
+        contract.retrieve_credits(ft_token_id.clone());
+        assert_eq!(
+            contract
+                .get_credits(SIGNER_ACCOUNT.to_string(), ft_token_id)
+                .0,
+            0
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Token usdc.testnet is disabled")]
+    fn test_disable_ft_token_panic_deposit() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 0);
+
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        contract.disable_ft_token(FT_ACCOUNT.to_string());
+        ft_deposit(&mut contract, SIGNER_ACCOUNT, 200_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner or config_manager can call this function")]
+    fn test_update_ft_token_assert_panic_no_owner() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 0);
+
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        contract.update_ft_token(FT_ACCOUNT.to_string(), ft_config(0, 1, 2));
+    }
+
+    #[test]
+    fn test_house_edge() {
+        assert_eq!(config::house_edge(4000, 500, 500, 200000), 5000);
+        assert_eq!(config::house_edge(0, 0, 0, 200000), 0);
+        assert_eq!(config::house_edge(0, 0, 0, 210000), -5000);
+        assert_eq!(
+            config::house_edge(4000, 500, 500, 0),
+            FRACTIONAL_BASE as i128
+        );
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        assert_eq!(contract.validate_config(), Ok(()));
+
+        contract.nft_fee = 99_500;
+        assert_eq!(
+            contract.validate_config(),
+            Err(ConfigError::FeesAboveBase {
+                total_fee: U128(100_500)
+            })
+        );
+        contract.nft_fee = 4000;
+
+        contract.min_bet = contract.max_bet + 1;
+        assert_eq!(
+            contract.validate_config(),
+            Err(ConfigError::MinBetAboveMaxBet {
+                min_bet: U128(contract.max_bet + 1),
+                max_bet: U128(contract.max_bet)
+            })
+        );
+        contract.min_bet = 0;
+
+        contract.min_balance_fraction = 0;
+        assert_eq!(
+            contract.validate_config(),
+            Err(ConfigError::ZeroMinBalanceFraction)
+        );
+        contract.min_balance_fraction = 100;
+
+        //negative edge for the house
+        contract.win_multiplier = 220000;
+        assert_eq!(
+            contract.validate_config(),
+            Err(ConfigError::HouseEdgeOutOfRange {
+                house_edge: I128(-4500),
+                min_house_edge: U128(0),
+                max_house_edge: U128(FRACTIONAL_BASE)
+            })
+        );
+    }
+
+    #[test]
+    fn test_update_house_edge_range() {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_house_edge_range(U128(1000), U128(6000));
+        assert_eq!(contract.get_house_edge_range().max_house_edge.0, 6000);
+        assert_eq!(contract.get_house_edge(None).0, 5000);
+
+        //7% edge is out of the declared range
+        contract.win_multiplier = 196000;
+        assert!(contract.validate_config().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "House edge of 5000 is outside the allowed range of 0 to 4000")]
+    fn test_update_house_edge_range_panic_current_config() {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_house_edge_range(U128(0), U128(4000));
+    }
+
+    #[test]
+    #[should_panic(expected = "min_balance_fraction must be greater than 0")]
+    fn test_update_contract_function_panic_zero_min_balance_fraction() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_contract(
+            U128(4000),
+            U128(500),
+            U128(500),
+            U128(200000),
+            U128(100),
+            U128(10),
+            U128(0),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "nft_fee, dev_fee and house_fee add up to 110000, above 100000")]
+    fn test_add_ft_token_panic_fees_above_base() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        contract.add_ft_token(FT_ACCOUNT.to_string(), ft_config(109_000, 1, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "At most 10 tokens can be whitelisted")]
+    fn test_add_ft_token_panic_max_tokens() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        for index in 0..fungible_tokens::MAX_FT_TOKENS {
+            contract.add_ft_token(format!("token{}.testnet", index), ft_config(4000, 1, 2));
+        }
+        //updating a whitelisted token is still allowed
+        contract.update_ft_token("token0.testnet".to_string(), ft_config(4000, 1, 3));
+        contract.add_ft_token(FT_ACCOUNT.to_string(), ft_config(4000, 1, 2));
+    }
+
+    #[test]
+    fn test_new_with_config() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let config = ContractConfig {
+            nft_fee: U128(4000),
+            dev_fee: U128(500),
+            house_fee: U128(500),
+            win_multiplier: U128(200000),
+            max_bet: U128(100_000_000),
+            min_bet: U128(100_000),
+            min_balance_fraction: U128(100),
+        };
+        let contract = SlotMachine::new(
+            OWNER_ACCOUNT.to_string(),
+            NFT_ACCOUNT.to_string(),
+            config.clone(),
+        );
+        assert_eq!(contract.get_config(), config);
+        //read from the nft contract by the callback of new
+        assert_eq!(contract.get_nft_contract().nft_mapping_size.0, 0);
+        assert_eq!(contract.get_contract_state()["win_multiplier"], "200000");
+        assert_eq!(contract.get_config_delay().0, timelock::MIN_CONFIG_DELAY);
+    }
+
+    #[test]
+    #[should_panic(expected = "min_bet 100 is above max_bet 10")]
+    fn test_new_panic_invalid_config() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        SlotMachine::new(
+            OWNER_ACCOUNT.to_string(),
+            NFT_ACCOUNT.to_string(),
+            ContractConfig {
+                nft_fee: U128(4000),
+                dev_fee: U128(500),
+                house_fee: U128(500),
+                win_multiplier: U128(200000),
+                max_bet: U128(10),
+                min_bet: U128(100),
+                min_balance_fraction: U128(100),
+            },
+        );
+    }
+
+    #[test]
+    fn test_update_config() {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        let before = contract.get_config();
+
+        let config = contract.update_config(ConfigPatch {
+            win_multiplier: Some(U128(190000)),
+            min_bet: Some(U128(200_000)),
+            ..Default::default()
+        });
+        assert_eq!(config.win_multiplier.0, 190000);
+        assert_eq!(config.min_bet.0, 200_000);
+        assert_eq!(config.nft_fee, before.nft_fee);
+        assert_eq!(config.max_bet, before.max_bet);
+        assert_eq!(contract.get_config(), config);
+
+        //only the fields that changed are logged
+        let events = get_events();
+        assert_eq!(events[0]["event"], "config_update");
+        assert_eq!(events[0]["data"].as_object().unwrap().len(), 2);
+        assert_eq!(events[0]["data"]["win_multiplier"], "190000");
+    }
+
+    #[test]
+    #[should_panic(expected = "nft_fee, dev_fee and house_fee add up to 100500, above 100000")]
+    fn test_update_config_panic_invalid() {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config(ConfigPatch {
+            nft_fee: Some(U128(99_500)),
+            ..Default::default()
+        });
+    }
+
+    const DAY: u64 = 86_400_000_000_000;
+
+    fn owner_context_at(block_timestamp: u64) -> VMContext {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        context.block_timestamp = block_timestamp;
+        context
+    }
+
+    #[test]
+    fn test_execute_config_after_delay() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(DAY));
+        assert_eq!(contract.get_config_delay().0, DAY);
+
+        let proposal = contract.propose_config(
+            ConfigPatch {
+                win_multiplier: Some(U128(190000)),
+                ..Default::default()
+            },
+            None,
+            None,
+            None,
+        );
+        assert_eq!(proposal.effective_at.0, DAY);
+        assert_eq!(contract.get_config_proposals().len(), 1);
+        let events = get_events();
+        assert_eq!(events[1]["event"], "config_proposed");
+        assert_eq!(events[1]["data"]["patch"]["win_multiplier"], "190000");
+        assert!(events[1]["data"]["patch"].get("nft_fee").is_none());
+
+        testing_env!(owner_context_at(DAY));
+        let config = contract.execute_config(proposal.id);
+        assert_eq!(config.win_multiplier.0, 190000);
+        assert_eq!(config.nft_fee.0, 4000);
+        assert!(contract.get_config_proposals().is_empty());
+        assert_eq!(get_events()[0]["data"]["win_multiplier"], "190000");
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal can only be executed from 86400000000000")]
+    fn test_execute_config_panic_before_delay() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(DAY));
+        let proposal = contract.propose_config(ConfigPatch::default(), None, None, None);
+
+        testing_env!(owner_context_at(DAY - 1));
+        contract.execute_config(proposal.id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposal can't take effect before 86400000000000")]
+    fn test_propose_config_panic_effective_before_delay() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(DAY));
+        contract.propose_config(ConfigPatch::default(), None, None, Some(U64(DAY - 1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "No config proposal with this id")]
+    fn test_cancel_config() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(DAY));
+        let proposal = contract.propose_config(ConfigPatch::default(), None, None, None);
+        contract.cancel_config(proposal.id);
+        assert!(contract.get_config_proposals().is_empty());
+        assert_eq!(get_events()[2]["event"], "config_proposal_canceled");
+
+        testing_env!(owner_context_at(DAY));
+        contract.execute_config(proposal.id);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Config changes must go through propose_config while config_delay is set"
+    )]
+    fn test_update_config_panic_config_delay() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(DAY));
+        contract.update_config(ConfigPatch {
+            win_multiplier: Some(U128(190000)),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "config_delay can only be lowered through propose_config")]
+    fn test_update_config_delay_panic_lower() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(DAY));
+        contract.update_config_delay(U64(DAY - 1));
+    }
+
+    #[test]
+    fn test_proposal_lowers_config_delay() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(2 * DAY));
+        let proposal = contract.propose_config(ConfigPatch::default(), None, Some(U64(DAY)), None);
+
+        testing_env!(owner_context_at(2 * DAY));
+        contract.execute_config(proposal.id);
+        assert_eq!(contract.get_config_delay().0, DAY);
+
+        //the next proposal already uses the lower delay
+        let proposal = contract.propose_config(
+            ConfigPatch {
+                win_multiplier: Some(U128(190000)),
+                ..Default::default()
+            },
+            None,
+            None,
+            None,
+        );
+        assert_eq!(proposal.effective_at.0, 3 * DAY);
+    }
+
+    #[test]
+    #[should_panic(expected = "config_delay can't be lower than 86400000000000")]
+    fn test_propose_config_panic_below_min_delay() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(2 * DAY));
+        contract.propose_config(ConfigPatch::default(), None, Some(U64(DAY - 1)), None);
+    }
+
+    #[test]
+    fn test_proposal_changes_token_and_bankroll_settings() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(DAY));
+
+        let proposal = contract.propose_config(
+            ConfigPatch {
+                max_bet_bankroll_fraction: Some(U128(1_000)),
+                ..Default::default()
+            },
+            Some(FtTokenConfig {
+                ft_token_id: FT_ACCOUNT.to_string(),
+                config: ft_config(4000, 1_000, 1_000_000),
+            }),
+            None,
+            None,
+        );
+        assert_eq!(
+            contract.get_config_proposals()[0]
+                .ft_token
+                .as_ref()
+                .unwrap()
+                .ft_token_id,
+            FT_ACCOUNT
+        );
+
+        testing_env!(owner_context_at(DAY));
+        contract.execute_config(proposal.id);
+        assert_eq!(contract.max_bet_bankroll_fraction, 1_000);
+        let asset = contract.get_ft_token(FT_ACCOUNT.to_string()).unwrap();
+        assert_eq!(asset.config.max_bet.0, 1_000_000);
+
+        //an accepted token is updated by the next proposal
+        let proposal = contract.propose_config(
+            ConfigPatch::default(),
+            Some(FtTokenConfig {
+                ft_token_id: FT_ACCOUNT.to_string(),
+                config: ft_config(2000, 1_000, 50_000),
+            }),
+            None,
+            None,
+        );
+        testing_env!(owner_context_at(2 * DAY));
+        contract.execute_config(proposal.id);
+        let asset = contract.get_ft_token(FT_ACCOUNT.to_string()).unwrap();
+        assert_eq!(asset.config.nft_fee.0, 2000);
+        assert_eq!(asset.config.max_bet.0, 50_000);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Config changes must go through propose_config while config_delay is set"
+    )]
+    fn test_add_ft_token_panic_config_delay() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(DAY));
+        contract.add_ft_token(FT_ACCOUNT.to_string(), ft_config(4000, 1_000, 1_000_000));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Config changes must go through propose_config while config_delay is set"
+    )]
+    fn test_update_ft_token_panic_config_delay() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 0);
+        testing_env!(owner_context_at(0));
+        contract.update_config_delay(U64(DAY));
+        contract.update_ft_token(FT_ACCOUNT.to_string(), ft_config(2000, 1_000, 50_000));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Config changes must go through propose_config while config_delay is set"
+    )]
+    fn test_update_max_bet_bankroll_fraction_panic_config_delay() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(DAY));
+        contract.update_max_bet_bankroll_fraction(U128(1_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "max_bet_bankroll_fraction of 100001 cannot be above 100000")]
+    fn test_update_max_bet_bankroll_fraction_panic_above_base() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_max_bet_bankroll_fraction(U128(FRACTIONAL_BASE + 1));
+    }
+
+    #[test]
+    fn test_pause_flags() {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+
+        let status = contract.pause(
+            String::from("investigating deposits"),
+            Some(PauseFlags {
+                deposits: true,
+                plays: false,
+                withdrawals: false,
+            }),
+        );
+        assert_eq!(status.reason, Some(String::from("investigating deposits")));
+        assert!(contract.paused.deposits);
+        assert!(!contract.paused.plays);
+        let events = get_events();
+        assert_eq!(events[0]["event"], "pause_update");
+        assert_eq!(events[0]["data"]["flags"]["deposits"], true);
+
+        //fields left out of flags are not paused
+        let flags: PauseFlags = near_sdk::serde_json::from_str(r#"{"plays": true}"#).unwrap();
+        contract.pause(String::from("investigating plays"), Some(flags));
+        assert!(!contract.paused.deposits);
+        assert!(contract.paused.plays);
+        assert!(!contract.paused.withdrawals);
+
+        //everything is paused when flags are left out
+        contract.pause(String::from("incident"), None);
+        assert_eq!(contract.get_pause_status().flags, PauseFlags::all());
+
+        let status = contract.unpause();
+        assert_eq!(status.flags, PauseFlags::default());
+        assert_eq!(status.reason, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Deposits are paused by owner: investigating deposits")]
+    fn test_deposit_panic_paused() {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.pause(
+            String::from("investigating deposits"),
+            Some(PauseFlags {
+                deposits: true,
+                plays: false,
+                withdrawals: false,
+            }),
+        );
+
+        let context = get_context(vec![], false, 1_000_000, 0);
+        testing_env!(context);
+        contract.deposit();
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner or operator can call this function")]
+    fn test_pause_panic_not_owner() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.pause(String::from("incident"), None);
+    }
+
+    #[test]
+    fn test_emergency_withdraw_surplus() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000_000);
+        contract.house_balance = 500_000;
+
+        // contract holds the player credits, the house funds and 200_000 sent to it directly
+        let storage_cost = env::storage_usage() as u128 * env::storage_byte_cost();
+        // the attached yoctonear is added to the account balance
+        let mut context = get_context(vec![], false, 1, storage_cost + 1_700_000 - 1);
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+
+        contract.emergency_withdraw(U128(400_000), None);
+        assert_eq!(contract.house_balance, 300_000);
+        assert_eq!(get_events()[0]["event"], "emergency_withdrawal");
+    }
+
+    #[test]
+    #[should_panic(expected = "Only 200000 yoctonear are above the contract's liabilities")]
+    fn test_emergency_withdraw_panic_storage_deposits() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+        let min_balance = contract.storage_balance_bounds().min.0;
+        let mut context = get_context(vec![], false, min_balance, 0);
+        context.predecessor_account_id = "other.testnet".to_string();
+        testing_env!(context);
+        contract.storage_deposit(None, None);
+        assert_eq!(contract.total_storage_deposits, min_balance);
+
+        //the storage balance of other.testnet is not surplus even though it uses
+        //less storage than it paid for
+        let storage_cost = env::storage_usage() as u128 * env::storage_byte_cost();
+        // the attached yoctonear is added to the account balance
+        let mut context = get_context(vec![], false, 1, storage_cost + min_balance + 200_000 - 1);
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        contract.emergency_withdraw(U128(200_001), None);
+    }
+
+    #[test]
+    fn test_failed_emergency_withdrawal_keeps_house_balance() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000_000);
+        contract.house_balance = 500_000;
+
+        let storage_cost = env::storage_usage() as u128 * env::storage_byte_cost();
+        // the attached yoctonear is added to the account balance
+        let mut context = get_context(vec![], false, 1, storage_cost + 1_700_000 - 1);
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        contract.emergency_withdraw(U128(400_000), None);
+        assert_eq!(contract.house_balance, 300_000);
+
+        //the amount stays in the contract as surplus, the bankroll is not credited with it
+        let mut context = get_context(vec![], false, 0, 0);
+        context.predecessor_account_id = CONTRACT_ACCOUNT.to_string();
+        testing_env!(
+            context,
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Failed]
+        );
+        assert!(!contract.on_transfer(
+            TransferKind::EmergencyWithdrawal,
+            SIGNER_ACCOUNT.to_string(),
+            U128(400_000)
+        ));
+        assert_eq!(contract.house_balance, 300_000);
+        let events = get_events();
+        assert_eq!(events[0]["event"], "transfer_failed");
+        assert_eq!(events[0]["data"]["kind"], "emergency_withdrawal");
+    }
+
+    #[test]
+    fn test_failed_ft_emergency_withdrawal_keeps_house_balance() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 5_000);
+
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        contract.emergency_withdraw(U128(3_000), Some(FT_ACCOUNT.to_string()));
+        let asset = contract.internal_ft_asset(&FT_ACCOUNT.to_string());
+        assert_eq!(asset.balance, 2_000);
+        assert_eq!(asset.house_balance, 2_000);
+
+        let mut context = get_context(vec![], false, 0, 0);
+        context.predecessor_account_id = CONTRACT_ACCOUNT.to_string();
+        testing_env!(
+            context,
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![near_sdk::PromiseResult::Failed]
+        );
+        assert!(!contract.on_ft_transfer(
+            TransferKind::EmergencyWithdrawal,
+            FT_ACCOUNT.to_string(),
+            OWNER_ACCOUNT.to_string(),
+            U128(3_000)
+        ));
+        let asset = contract.internal_ft_asset(&FT_ACCOUNT.to_string());
+        assert_eq!(asset.balance, 5_000);
+        assert_eq!(asset.house_balance, 2_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only 700000 yoctonear are above the contract's liabilities")]
+    fn test_emergency_withdraw_panic_liabilities() {
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        let mut contract = get_contract(SIGNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000_000);
+        contract.house_balance = 500_000;
+
+        let storage_cost = env::storage_usage() as u128 * env::storage_byte_cost();
+        // the attached yoctonear is added to the account balance
+        let mut context = get_context(vec![], false, 1, storage_cost + 1_700_000 - 1);
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+
+        contract.emergency_withdraw(U128(700_001), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only 5000 are above the contract's liabilities")]
+    fn test_emergency_withdraw_ft_panic_liabilities() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 5_000);
+        ft_deposit(&mut contract, SIGNER_ACCOUNT, 100_000);
+
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        contract.emergency_withdraw(U128(5_001), Some(FT_ACCOUNT.to_string()));
+    }
+
+    #[test]
+    fn test_retrieve_credits_while_paused() {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000_000);
+        contract.pause(String::from("incident"), None);
+
+        // contract holds the credits it sends back
+        let storage_cost = env::storage_usage() as u128 * env::storage_byte_cost();
+        let context = get_context(vec![], false, 0, storage_cost + 1_000_000);
+        testing_env!(context);
+        contract.retrieve_credits(None);
+        assert_eq!(contract.get_credits(SIGNER_ACCOUNT.to_string(), None).0, 0);
+        assert_eq!(contract.total_credits, 0);
+    }
+
+    const OPERATOR_ACCOUNT: &str = "operator.testnet";
+
+    fn grant_role(contract: &mut SlotMachine, account_id: &str, role: Role) {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        contract.grant_role(account_id.to_string(), role);
+    }
+
+    fn operator_context() -> VMContext {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OPERATOR_ACCOUNT.to_string();
+        context
+    }
+
+    #[test]
+    fn test_grant_role() {
+        testing_env!(get_context(vec![], false, 1, 0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        grant_role(&mut contract, OPERATOR_ACCOUNT, Role::Operator);
+        grant_role(&mut contract, OPERATOR_ACCOUNT, Role::ConfigManager);
+        assert_eq!(get_events()[0]["event"], "role_granted");
+        assert_eq!(get_events()[0]["data"]["role"], "config_manager");
+        assert_eq!(
+            contract.get_roles(OPERATOR_ACCOUNT.to_string()),
+            vec![Role::Operator, Role::ConfigManager]
+        );
+        let holders = contract.get_role_holders();
+        assert_eq!(holders.len(), 1);
+        assert_eq!(holders[0].account_id, OPERATOR_ACCOUNT);
+
+        testing_env!(operator_context());
+        contract.pause(String::from("incident"), None);
+        contract.unpause();
+        contract.update_config(ConfigPatch {
+            win_multiplier: Some(U128(190000)),
+            ..Default::default()
+        });
+        assert_eq!(contract.win_multiplier, 190000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner or operator can call this function")]
+    fn test_revoke_role() {
+        testing_env!(get_context(vec![], false, 1, 0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        grant_role(&mut contract, OPERATOR_ACCOUNT, Role::Operator);
+
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        contract.revoke_role(OPERATOR_ACCOUNT.to_string(), Role::Operator);
+        assert!(contract.get_roles(OPERATOR_ACCOUNT.to_string()).is_empty());
+        assert!(contract.get_role_holders().is_empty());
+
+        testing_env!(operator_context());
+        contract.pause(String::from("incident"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner or fee_collector can call this function")]
+    fn test_role_does_not_grant_other_roles() {
+        testing_env!(get_context(vec![], false, 1, 0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        grant_role(&mut contract, OPERATOR_ACCOUNT, Role::Operator);
+
+        testing_env!(operator_context());
+        contract.retrieve_dev_funds(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can call this function")]
+    fn test_grant_role_panic_not_owner() {
+        testing_env!(get_context(vec![], false, 1, 0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        grant_role(&mut contract, OPERATOR_ACCOUNT, Role::Operator);
+
+        testing_env!(operator_context());
+        contract.grant_role("other.testnet".to_string(), Role::ConfigManager);
+    }
+
+    #[test]
+    fn test_fee_collector_retrieves_dev_funds() {
+        testing_env!(get_context(vec![], false, 1, 0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        grant_role(&mut contract, OPERATOR_ACCOUNT, Role::FeeCollector);
+        contract.dev_balance = 1_000;
+
+        // contract holds the dev funds it sends out
+        let mut context = operator_context();
+        context.account_balance = 1_000_000_000_000_000_000_000_000;
+        testing_env!(context);
+        contract.retrieve_dev_funds(None);
+        assert_eq!(contract.dev_balance, 0);
+        let events = get_events();
+        assert_eq!(events[0]["event"], "dev_funds_withdrawal");
+        assert_eq!(events[0]["data"]["account_id"], OPERATOR_ACCOUNT);
+    }
+
+    #[test]
+    fn test_ownership_transfer() {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.propose_owner("dao.testnet".to_string());
+        assert_eq!(
+            contract.get_pending_owner(),
+            Some("dao.testnet".to_string())
+        );
+        //proposing does not hand over any rights
+        assert_eq!(contract.owner_id, OWNER_ACCOUNT);
+        assert_eq!(get_events()[0]["event"], "ownership_proposed");
+
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = "dao.testnet".to_string();
+        testing_env!(context);
+        contract.accept_ownership();
+        assert_eq!(contract.owner_id, "dao.testnet");
+        assert_eq!(contract.get_pending_owner(), None);
+        let events = get_events();
+        assert_eq!(events[0]["event"], "ownership_transferred");
+        assert_eq!(events[0]["data"]["old_owner_id"], OWNER_ACCOUNT);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the proposed owner can accept ownership")]
+    fn test_accept_ownership_panic_not_proposed() {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.propose_owner("dao.testnet".to_string());
+
+        let context = get_context(vec![], false, 1, 0);
+        testing_env!(context);
+        contract.accept_ownership();
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the proposed owner can accept ownership")]
+    fn test_cancel_owner_proposal() {
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.propose_owner("dao.testnet".to_string());
+        contract.cancel_owner_proposal();
+        assert_eq!(contract.get_pending_owner(), None);
+
+        let mut context = get_context(vec![], false, 1, 0);
+        context.predecessor_account_id = "dao.testnet".to_string();
+        testing_env!(context);
+        contract.accept_ownership();
+    }
+
+    fn write_v1_state(panic_button: bool) -> SlotMachineV1 {
+        let mut old = SlotMachineV1 {
+            owner_id: OWNER_ACCOUNT.to_string(),
+            credits: LookupMap::new(b"credits".to_vec()),
+            nft_fee: 4000,
+            dev_fee: 500,
+            house_fee: 500,
+            win_multiplier: 200000,
+            nft_balance: 1_000,
+            dev_balance: 2_000,
+            max_bet: 100_000_000,
+            min_bet: 100_000,
+            min_balance_fraction: 100,
+            panic_button,
+        };
+        old.credits.insert(&SIGNER_ACCOUNT.to_string(), &300_000);
+        old.credits.insert(&"other.testnet".to_string(), &700_000);
+        env::state_write(&old);
+        old
+    }
+
+    fn v1_migration_args(total_credits: u128) -> V1MigrationArgs {
+        V1MigrationArgs {
+            nft_id: NFT_ACCOUNT.to_string(),
+            total_credits: U128(total_credits),
+        }
+    }
+
+    //contract holds the credits, the nft and dev balances and house funds on top
+    fn v1_balance_context(house_funds: u128) -> VMContext {
+        let storage_cost = env::storage_usage() as u128 * env::storage_byte_cost();
+        let mut context = get_context(vec![], false, 0, storage_cost + 1_003_000 + house_funds);
+        context.predecessor_account_id = CONTRACT_ACCOUNT.to_string();
+        context.storage_usage = env::storage_usage();
+        context
+    }
+
+    //owner call on a contract holding the V1 balances, storage is measured as it is now
+    fn v1_owner_context(house_funds: u128) -> VMContext {
+        let mut context = v1_balance_context(house_funds);
+        context.predecessor_account_id = OWNER_ACCOUNT.to_string();
+        context.attached_deposit = 1;
+        context
+    }
+
+    fn index_v1_credit_holders(contract: &mut SlotMachine, house_funds: u128) {
+        testing_env!(v1_owner_context(house_funds));
+        contract.index_credit_holders(vec![
+            SIGNER_ACCOUNT.to_string(),
+            "other.testnet".to_string(),
+        ]);
+        testing_env!(v1_owner_context(house_funds));
+    }
+
+    #[test]
+    fn test_migrate_v1() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        write_v1_state(false);
+        testing_env!(v1_balance_context(50_000));
+
+        let mut contract = SlotMachine::migrate(Some(v1_migration_args(1_000_000)));
+        //credits only count once their holders are indexed, everything is paused until then
+        assert_eq!(contract.total_credits, 0);
+        assert_eq!(contract.house_balance, 0);
+        assert_eq!(contract.get_pause_status().flags, PauseFlags::all());
+        assert_eq!(
+            contract.get_v1_migration(),
+            Some(V1MigrationView {
+                total_credits: U128(1_000_000),
+                indexed_credits: U128(0)
+            })
+        );
+
+        index_v1_credit_holders(&mut contract, 50_000);
+        let status = contract.finish_v1_migration();
+        assert_eq!(status.flags, PauseFlags::default());
+        assert_eq!(contract.get_v1_migration(), None);
+        assert_eq!(contract.owner_id, OWNER_ACCOUNT);
+        assert_eq!(contract.nft_id, NFT_ACCOUNT);
+        assert_eq!(
+            contract.get_credits(SIGNER_ACCOUNT.to_string(), None).0,
+            300_000
+        );
+        assert_eq!(
+            contract.get_credits("other.testnet".to_string(), None).0,
+            700_000
+        );
+        assert_eq!(contract.total_credits, 1_000_000);
+        assert_eq!(contract.dev_balance, 2_000);
+        assert_eq!(contract.nft_balance, 1_000);
+        //undistributed nft fees wait for the nft supply
+        assert_eq!(contract.get_nft_claimable("1".to_string(), None).0, 0);
+        nft_supply_callback(&mut contract, 10);
+        assert_eq!(contract.get_nft_claimable("1".to_string(), None).0, 100);
+        //the yocto attached to finish_v1_migration is held above the liabilities too
+        assert_eq!(contract.house_balance, 50_001);
+        assert_eq!(contract.get_config().win_multiplier.0, 200000);
+        assert_eq!(contract.get_pause_status().flags, PauseFlags::default());
+        assert_eq!(contract.get_config_delay().0, timelock::MIN_CONFIG_DELAY);
+        assert!(contract.account_storage_usage > 0);
+    }
+
+    #[test]
+    fn test_migrate_v1_keeps_panic_mode() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        write_v1_state(true);
+        testing_env!(v1_balance_context(0));
+
+        let mut contract = SlotMachine::migrate(Some(v1_migration_args(1_000_000)));
+        index_v1_credit_holders(&mut contract, 0);
+        let status = contract.finish_v1_migration();
+        assert_eq!(status.flags, PauseFlags::all());
+        assert_eq!(
+            status.reason,
+            Some(String::from("Paused before the upgrade"))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Indexed credits add up to 300000 of the 1000000 announced")]
+    fn test_finish_v1_migration_panic_credits_not_indexed() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        write_v1_state(false);
+        testing_env!(v1_balance_context(0));
+        let mut contract = SlotMachine::migrate(Some(v1_migration_args(1_000_000)));
+
+        testing_env!(v1_owner_context(0));
+        contract.index_credit_holders(vec![SIGNER_ACCOUNT.to_string()]);
+        testing_env!(v1_owner_context(0));
+        contract.finish_v1_migration();
+    }
+
+    #[test]
+    #[should_panic(expected = "Indexed credits add up to 1000000 of the 900000 announced")]
+    fn test_finish_v1_migration_panic_total_credits_too_low() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        write_v1_state(false);
+        testing_env!(v1_balance_context(0));
+        let mut contract = SlotMachine::migrate(Some(v1_migration_args(900_000)));
+
+        index_v1_credit_holders(&mut contract, 0);
+        contract.finish_v1_migration();
+    }
+
+    #[test]
+    fn test_v1_credits_indexed_when_retrieved() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        write_v1_state(false);
+        testing_env!(v1_balance_context(0));
+        let mut contract = SlotMachine::migrate(Some(v1_migration_args(1_000_000)));
+
+        //retrieving credits is allowed while paused, they are counted before leaving
+        let mut context = v1_balance_context(0);
+        context.predecessor_account_id = "other.testnet".to_string();
+        testing_env!(context);
+        contract.retrieve_credits(None);
+        assert_eq!(contract.total_credits, 0);
+        assert_eq!(
+            contract.get_v1_migration().unwrap().indexed_credits.0,
+            700_000
+        );
+
+        testing_env!(v1_owner_context(0));
+        contract.index_credit_holders(vec![SIGNER_ACCOUNT.to_string()]);
+        testing_env!(v1_owner_context(0));
+        contract.finish_v1_migration();
+        assert_eq!(contract.total_credits, 300_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Migration from V1 must be finished first")]
+    fn test_unpause_panic_during_v1_migration() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        write_v1_state(false);
+        testing_env!(v1_balance_context(0));
+        let mut contract = SlotMachine::migrate(Some(v1_migration_args(1_000_000)));
+
+        testing_env!(v1_owner_context(0));
+        contract.unpause();
+    }
+
+    #[test]
+    #[should_panic(expected = "Migration from V1 must be finished first")]
+    fn test_emergency_withdraw_panic_during_v1_migration() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        write_v1_state(false);
+        testing_env!(v1_balance_context(50_000));
+        let mut contract = SlotMachine::migrate(Some(v1_migration_args(1_000_000)));
+
+        testing_env!(v1_owner_context(50_000));
+        contract.emergency_withdraw(U128(1), None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "total_credits and the nft and dev balances add up to more than the contract holds"
+    )]
+    fn test_migrate_v1_panic_total_credits_above_balance() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        write_v1_state(false);
+        testing_env!(v1_balance_context(0));
+
+        SlotMachine::migrate(Some(v1_migration_args(1_000_001)));
+    }
+
+    #[test]
+    #[should_panic(expected = "from_v1 is required to migrate V1 state")]
+    fn test_migrate_v1_panic_no_args() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        write_v1_state(false);
+        SlotMachine::migrate(None);
+    }
+
+    #[test]
+    fn test_migrate_current_version() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 1_000_000);
+        contract.house_balance = 5_000;
+        env::state_write(&contract);
+        migrate::write_state_version();
+
+        let contract = SlotMachine::migrate(None);
+        assert_eq!(
+            contract.get_credits(SIGNER_ACCOUNT.to_string(), None).0,
+            1_000_000
+        );
+        assert_eq!(contract.total_credits, 1_000_000);
+        assert_eq!(contract.house_balance, 5_000);
+        //contracts deployed without a delay get the minimum one
+        assert_eq!(contract.get_config_delay().0, timelock::MIN_CONFIG_DELAY);
+    }
+
+    const STAGE_DEPOSIT: u128 = 1_000_000_000_000_000_000_000_000;
+
+    //owner call on a contract holding the deposits of the staged code
+    fn staged_owner_context(block_timestamp: u64) -> VMContext {
+        let mut context = owner_context_at(block_timestamp);
+        context.account_balance = STAGE_DEPOSIT;
+        context
+    }
+
+    fn stage_code(contract: &mut SlotMachine, block_timestamp: u64, code: &[u8]) -> String {
+        let mut context = staged_owner_context(block_timestamp);
+        context.attached_deposit = STAGE_DEPOSIT;
+        testing_env!(context);
+        contract.stage_upgrade(Base64VecU8(code.to_vec())).code_hash
+    }
+
+    fn stage_upgrade(contract: &mut SlotMachine, block_timestamp: u64) -> String {
+        stage_code(contract, block_timestamp, b"new release")
+    }
+
+    #[test]
+    fn test_upgrade() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(DAY));
+        let code_hash = stage_upgrade(&mut contract, 0);
+        assert_eq!(code_hash.len(), 64);
+        let staged_upgrade = contract.get_staged_upgrade().unwrap();
+        assert_eq!(staged_upgrade.executable_at.0, DAY);
+        assert_eq!(get_events()[0]["event"], "upgrade_staged");
+
+        testing_env!(staged_owner_context(DAY));
+        contract.upgrade(code_hash.clone(), None);
+        assert!(contract.get_staged_upgrade().is_some());
+
+        //the staged code is only dropped once the deployment succeeded
+        upgrade_callback_context(near_sdk::PromiseResult::Successful(vec![]));
+        assert!(contract.on_upgrade(code_hash));
+        assert!(contract.get_staged_upgrade().is_none());
+        assert!(env::storage_read(b"staged_code").is_none());
+        assert_eq!(get_events()[0]["event"], "upgrade_deployed");
+    }
+
+    fn upgrade_callback_context(promise_result: near_sdk::PromiseResult) {
+        let mut context = staged_owner_context(DAY);
+        context.predecessor_account_id = CONTRACT_ACCOUNT.to_string();
+        context.attached_deposit = 0;
+        testing_env!(
+            context,
+            near_sdk::VMConfig::default(),
+            near_sdk::RuntimeFeesConfig::default(),
+            Default::default(),
+            vec![promise_result]
+        );
+    }
+
+    #[test]
+    fn test_failed_upgrade_keeps_staged_code() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        let code_hash = stage_upgrade(&mut contract, 0);
+
+        testing_env!(staged_owner_context(0));
+        contract.upgrade(code_hash.clone(), None);
+        upgrade_callback_context(near_sdk::PromiseResult::Failed);
+        assert!(!contract.on_upgrade(code_hash.clone()));
+        assert_eq!(contract.get_staged_upgrade().unwrap().code_hash, code_hash);
+        assert_eq!(
+            env::storage_read(b"staged_code"),
+            Some(b"new release".to_vec())
+        );
+        assert_eq!(get_events()[0]["event"], "upgrade_failed");
+
+        //the upgrade can be executed again
+        testing_env!(staged_owner_context(0));
+        contract.upgrade(code_hash, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Upgrade can only be executed from 86400000000000")]
+    fn test_upgrade_panic_before_delay() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.update_config_delay(U64(DAY));
+        let code_hash = stage_upgrade(&mut contract, 0);
+
+        testing_env!(owner_context_at(DAY - 1));
+        contract.upgrade(code_hash, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "code_hash does not match the staged code")]
+    fn test_upgrade_panic_hash_mismatch() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        stage_upgrade(&mut contract, 0);
+
+        testing_env!(owner_context_at(0));
+        contract.upgrade("00".repeat(32), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "There is no staged upgrade")]
+    fn test_cancel_upgrade() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        let code_hash = stage_upgrade(&mut contract, 0);
+
+        testing_env!(staged_owner_context(0));
+        contract.cancel_upgrade();
+        assert!(contract.get_staged_upgrade().is_none());
+        contract.upgrade(code_hash, None);
+    }
+
+    #[test]
+    fn test_stage_upgrade_refunds_released_storage() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        stage_code(&mut contract, 0, &[0; 1_000]);
+
+        //smaller code gives back the storage of the bytes it no longer uses
+        stage_code(&mut contract, 0, &[0; 400]);
+        let refund = STAGE_DEPOSIT - 1 + 600 * env::storage_byte_cost();
+        let receipts = format!("{:?}", near_sdk::test_utils::get_created_receipts());
+        assert!(receipts.contains(&format!("deposit: {} ", refund)));
+        assert_eq!(env::storage_read(b"staged_code"), Some([0; 400].to_vec()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Upgrade needs more than")]
+    fn test_upgrade_panic_not_enough_gas() {
+        testing_env!(owner_context_at(0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        let code_hash = stage_upgrade(&mut contract, 0);
+
+        let mut context = staged_owner_context(0);
+        context.prepaid_gas = 40_000_000_000_000;
+        testing_env!(context);
+        contract.upgrade(code_hash, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can call this function")]
+    fn test_stage_upgrade_panic_not_owner() {
+        testing_env!(get_context(vec![], false, STAGE_DEPOSIT, 0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.stage_upgrade(Base64VecU8(b"new release".to_vec()));
+    }
+
+    #[test]
+    fn test_player_stats_and_bet_history() {
+        let mut context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context.clone());
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 100_000_000);
+        contract.house_balance = 10_000_000;
+
+        let bet_sizes: [u128; 3] = [100_000, 200_000, 300_000];
+        let mut results = vec![];
+        for (index, bet_size) in bet_sizes.iter().enumerate() {
+            context.block_index = index as u64 * RESOLVE_DELAY_BLOCKS;
+            context.storage_usage = env::storage_usage();
+            testing_env!(context.clone());
+            contract.play(index % 2 == 0, U128(*bet_size), None);
+            context.block_index += RESOLVE_DELAY_BLOCKS;
+            context.storage_usage = env::storage_usage();
+            testing_env!(context.clone());
+            results.push(contract.resolve_bet(SIGNER_ACCOUNT.to_string()));
+        }
+
+        let stats = contract.get_player_stats(SIGNER_ACCOUNT.to_string(), None);
+        let wins = results.iter().filter(|result| result.won).count() as u64;
+        let payouts: Vec<u128> = results.iter().map(|result| result.payout.0).collect();
+        assert_eq!(stats.bets.0, 3);
+        assert_eq!(stats.wins.0, wins);
+        assert_eq!(stats.total_wagered.0, 600_000);
+        assert_eq!(stats.total_won.0, payouts.iter().sum::<u128>());
+        assert_eq!(stats.biggest_win.0, *payouts.iter().max().unwrap());
+        let last_won = results[2].won;
+        let streak = results
+            .iter()
+            .rev()
+            .take_while(|result| result.won == last_won)
+            .count() as i64;
+        assert_eq!(
+            stats.current_streak.0,
+            if last_won { streak } else { -streak }
+        );
+        let ft_stats =
+            contract.get_player_stats(SIGNER_ACCOUNT.to_string(), Some(FT_ACCOUNT.to_string()));
+        assert_eq!(ft_stats.bets.0, 0);
+
+        //most recent bet first
+        let history = contract.get_bet_history(SIGNER_ACCOUNT.to_string(), None, None);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].bet_size.0, 300_000);
+        assert_eq!(history[0].chosen_side, CoinSide::Heads);
+        assert_eq!(history[0].won, results[2].won);
+        assert_eq!(history[0].resolve_block.0, 3 * RESOLVE_DELAY_BLOCKS);
+        assert_eq!(history[2].bet_size.0, 100_000);
+
+        let page = contract.get_bet_history(SIGNER_ACCOUNT.to_string(), Some(1), Some(1));
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].bet_size.0, 200_000);
+        assert_eq!(page[0].chosen_side, CoinSide::Tails);
+        assert!(contract
+            .get_bet_history(SIGNER_ACCOUNT.to_string(), Some(3), None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_bet_history_ring_buffer() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+
+        let mut history = BetHistory::default();
+        for resolve_block in 0..(BET_HISTORY_SIZE as u64 + 5) {
+            history.push(BetRecord {
+                bet_type: true,
+                won: false,
+                bet_size: 100_000,
+                payout: 0,
+                resolve_block,
+                ft_token_id: None,
+            });
+        }
+        assert_eq!(history.records.len(), BET_HISTORY_SIZE);
+        contract
+            .bet_history
+            .insert(&SIGNER_ACCOUNT.to_string(), &history);
+
+        testing_env!(get_context(vec![], true, 0, 0));
+        //only the last BET_HISTORY_SIZE bets are kept
+        let bets = contract.get_bet_history(SIGNER_ACCOUNT.to_string(), None, Some(100));
+        let resolve_blocks: Vec<u64> = bets.iter().map(|bet| bet.resolve_block.0).collect();
+        let expected: Vec<u64> = (5..(BET_HISTORY_SIZE as u64 + 5)).rev().collect();
+        assert_eq!(resolve_blocks, expected);
+
+        let page = contract.get_bet_history(SIGNER_ACCOUNT.to_string(), Some(18), Some(5));
+        let resolve_blocks: Vec<u64> = page.iter().map(|bet| bet.resolve_block.0).collect();
+        assert_eq!(resolve_blocks, vec![6, 5]);
+    }
+
+    #[test]
+    fn test_storage_unregister_removes_player_records() {
+        let mut context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context.clone());
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 100_000_000);
+        contract.house_balance = 10_000_000;
+        contract.play(true, U128(100_000), None);
+        context.block_index = RESOLVE_DELAY_BLOCKS;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.resolve_bet(SIGNER_ACCOUNT.to_string());
+
+        context.attached_deposit = 1;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        assert!(contract.storage_unregister(Some(true)));
+        let stats = contract.get_player_stats(SIGNER_ACCOUNT.to_string(), None);
+        assert_eq!(stats.bets.0, 0);
+        assert!(contract
+            .get_bet_history(SIGNER_ACCOUNT.to_string(), None, None)
+            .is_empty());
+
+        //nothing is left of the account, it is counted again after registering again
+        context.attached_deposit = 1_000_000_000_000_000_000_000_000;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.storage_deposit(None, None);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 100_000_000);
+        context.attached_deposit = 0;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.play(true, U128(100_000), None);
+        context.block_index = 2 * RESOLVE_DELAY_BLOCKS;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        contract.resolve_bet(SIGNER_ACCOUNT.to_string());
+        let game_stats = contract.get_game_stats(None);
+        assert_eq!(game_stats.total_bets.0, 2);
+        assert_eq!(game_stats.unique_players.0, 2);
+    }
+
+    #[test]
+    fn test_game_stats() {
+        let mut context = get_context(vec![], false, 0, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context.clone());
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        insert_credits(&mut contract, SIGNER_ACCOUNT, 100_000_000);
+        insert_credits(&mut contract, OWNER_ACCOUNT, 100_000_000);
+        contract.house_balance = 10_000_000;
+
+        //two bets by the same player and one by another
+        let bets = [
+            (SIGNER_ACCOUNT, 100_000),
+            (SIGNER_ACCOUNT, 200_000),
+            (OWNER_ACCOUNT, 300_000),
+        ];
+        let mut payouts = 0;
+        for (index, (account_id, bet_size)) in bets.iter().enumerate() {
+            context.predecessor_account_id = account_id.to_string();
+            context.block_index = index as u64 * RESOLVE_DELAY_BLOCKS;
+            context.storage_usage = env::storage_usage();
+            testing_env!(context.clone());
+            contract.play(true, U128(*bet_size), None);
+            context.block_index += RESOLVE_DELAY_BLOCKS;
+            context.storage_usage = env::storage_usage();
+            testing_env!(context.clone());
+            payouts += contract.resolve_bet(account_id.to_string()).payout.0;
+        }
+
+        let game_stats = contract.get_game_stats(None);
+        assert_eq!(game_stats.total_bets.0, 3);
+        assert_eq!(game_stats.total_volume.0, 600_000);
+        assert_eq!(game_stats.total_payouts.0, payouts);
+        assert_eq!(game_stats.nft_fees.0, 600_000 * 4000 / FRACTIONAL_BASE);
+        assert_eq!(game_stats.dev_fees.0, 600_000 * 500 / FRACTIONAL_BASE);
+        assert_eq!(game_stats.house_fees.0, 600_000 * 500 / FRACTIONAL_BASE);
+        assert_eq!(game_stats.unique_players.0, 2);
+
+        let events = get_events();
+        let event = events
+            .iter()
+            .find(|event| event["event"] == "game_stats_update")
+            .unwrap();
+        assert_eq!(event["data"]["total_bets"], "3");
+        assert_eq!(event["data"]["unique_players"], "2");
+        assert!(event["data"].get("ft_token_id").is_none());
+    }
+
+    #[test]
+    fn test_ft_game_stats() {
+        let context = get_context(vec![], false, 0, 0);
+        testing_env!(context);
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        add_ft_token(&mut contract, 5_000_000);
+        ft_deposit(&mut contract, SIGNER_ACCOUNT, 200_000);
+        let ft_token_id = Some(FT_ACCOUNT.to_string());
+
+        let mut context = get_context(vec![], false, 0, 0);
+        testing_env!(context.clone());
+        contract.play(true, U128(100_000), ft_token_id.clone());
+        context.block_index = RESOLVE_DELAY_BLOCKS;
+        testing_env!(context);
+        let bet_result = contract.resolve_bet(SIGNER_ACCOUNT.to_string());
+
+        //bets in a token are counted apart from bets in near
+        let game_stats = contract.get_game_stats(ft_token_id.clone());
+        assert_eq!(game_stats.total_bets.0, 1);
+        assert_eq!(game_stats.total_volume.0, 100_000);
+        assert_eq!(game_stats.total_payouts.0, bet_result.payout.0);
+        assert_eq!(game_stats.nft_fees.0, bet_result.nft_fee.0);
+        assert_eq!(game_stats.unique_players.0, 1);
+        assert_eq!(contract.get_game_stats(None).total_bets.0, 0);
+
+        let events = get_events();
+        let event = events
+            .iter()
+            .find(|event| event["event"] == "game_stats_update")
+            .unwrap();
+        assert_eq!(event["data"]["ft_token_id"], FT_ACCOUNT);
+    }
+
+    #[test]
+    fn test_credit_holders() {
+        let mut context = get_context(vec![], false, 10_000_000, 1_000_000_000_000_000_000_000_000);
+        testing_env!(context.clone());
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.house_balance = 10_000_000;
+        contract.deposit();
+        context.predecessor_account_id = "other.testnet".to_string();
+        context.attached_deposit = 20_000_000;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract
+            .storage_deposits
+            .insert(&"other.testnet".to_string(), &0);
+        contract.deposit();
+        contract.play(true, U128(100_000), None);
+
+        //holders add up to total_credits
+        assert_eq!(contract.get_credit_holders_count().0, 2);
+        let holders = contract.get_credit_holders(None, None);
+        assert_eq!(
+            holders,
+            vec![
+                CreditHolderView {
+                    account_id: SIGNER_ACCOUNT.to_string(),
+                    credits: U128(10_000_000)
+                },
+                CreditHolderView {
+                    account_id: "other.testnet".to_string(),
+                    credits: U128(19_900_000)
+                }
+            ]
+        );
+        let total: u128 = holders.iter().map(|holder| holder.credits.0).sum();
+        assert_eq!(total, contract.total_credits);
+        let page = contract.get_credit_holders(Some(1), Some(1));
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].account_id, "other.testnet");
+
+        //accounts that retrieve all their credits leave the index
+        context.predecessor_account_id = SIGNER_ACCOUNT.to_string();
+        context.attached_deposit = 0;
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+        contract.retrieve_credits(None);
+        let holders = contract.get_credit_holders(None, None);
+        assert_eq!(holders.len(), 1);
+        assert_eq!(holders[0].account_id, "other.testnet");
+        assert_eq!(holders[0].credits.0, contract.total_credits);
+    }
+
+    #[test]
+    fn test_index_credit_holders() {
+        testing_env!(get_context(vec![], false, 0, 0));
+        write_v1_state(false);
+        testing_env!(v1_balance_context(0));
+        let mut contract = SlotMachine::migrate(Some(v1_migration_args(1_000_000)));
+        assert_eq!(contract.get_credit_holders_count().0, 0);
+
+        //accounts without credits and accounts already indexed are skipped
+        testing_env!(v1_owner_context(0));
+        let added = contract.index_credit_holders(vec![
+            SIGNER_ACCOUNT.to_string(),
+            "other.testnet".to_string(),
+            "other.testnet".to_string(),
+            OWNER_ACCOUNT.to_string(),
+        ]);
+        assert_eq!(added.0, 2);
+        let total: u128 = contract
+            .get_credit_holders(None, None)
+            .iter()
+            .map(|holder| holder.credits.0)
+            .sum();
+        assert_eq!(total, contract.total_credits);
+        assert_eq!(total, 1_000_000);
+        assert_eq!(
+            contract.get_v1_migration().unwrap().indexed_credits.0,
+            1_000_000
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can call this function")]
+    fn test_index_credit_holders_panic_no_owner() {
+        testing_env!(get_context(vec![], false, 1, 0));
+        let mut contract = get_contract(OWNER_ACCOUNT, 4000, 500, 500, 200000);
+        contract.index_credit_holders(vec![SIGNER_ACCOUNT.to_string()]);
+    }
+
     //functions that use cross contract calls are tested using sim-tests
 }
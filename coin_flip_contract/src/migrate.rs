@@ -0,0 +1,277 @@
+use crate::*;
+
+// storage key of the layout the contract state was last written with, contracts
+// deployed before versioning don't have it
+const STATE_VERSION_KEY: &[u8] = b"state_version";
+
+// layouts SlotMachine has been stored with, a variant is added every time
+// a release changes the struct or a type stored in it
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy, PartialEq, Debug)]
+pub enum StateVersion {
+    V1,
+    // first versioned release. Every field added to SlotMachine, ConfigProposal,
+    // FtAsset and the other stored types up to this release is part of V2, the
+    // development builds in between were never deployed. Changes after the release add V3
+    V2,
+}
+
+pub const CURRENT_STATE_VERSION: StateVersion = StateVersion::V2;
+
+// SlotMachine as first deployed, before the state was versioned
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SlotMachineV1 {
+    pub owner_id: AccountId,
+    pub credits: LookupMap<AccountId, Balance>,
+    pub nft_fee: u128,
+    pub dev_fee: u128,
+    pub house_fee: u128,
+    pub win_multiplier: u128,
+    pub nft_balance: u128,
+    pub dev_balance: u128,
+    pub max_bet: u128,
+    pub min_bet: u128,
+    pub min_balance_fraction: u128,
+    pub panic_button: bool,
+}
+
+// contract state in any of its layouts
+pub enum VersionedSlotMachine {
+    V1(SlotMachineV1),
+    V2(Box<SlotMachine>),
+}
+
+// what V1 did not store and migrate needs to build the current layout, the size of
+// the nft collection is read from the nft contract.
+// total_credits is the sum of the credits held by all accounts, which V1 could not iterate.
+// It is only checked against the contract balance, the migration is finished once the
+// credits of the indexed holders add up to it
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct V1MigrationArgs {
+    pub nft_id: AccountId,
+    pub total_credits: U128,
+}
+
+// upgrade from V1 in progress. Credits are counted in total_credits as their holders are
+// indexed, operations stay paused until indexed_credits adds up to total_credits
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct V1Migration {
+    pub total_credits: u128, // sum of the V1 credits announced in V1MigrationArgs
+    pub indexed_credits: u128, // V1 credits of the holders indexed so far
+    pub panic_button: bool,  // pause state of V1, restored when the migration is finished
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct V1MigrationView {
+    pub total_credits: U128,
+    pub indexed_credits: U128,
+}
+
+impl VersionedSlotMachine {
+    pub fn read() -> Self {
+        let version = match env::storage_read(STATE_VERSION_KEY) {
+            Some(bytes) => StateVersion::try_from_slice(&bytes).expect("Invalid state version"),
+            None => StateVersion::V1,
+        };
+        match version {
+            StateVersion::V1 => {
+                VersionedSlotMachine::V1(env::state_read().expect("Contract is not initialized"))
+            }
+            StateVersion::V2 => VersionedSlotMachine::V2(Box::new(
+                env::state_read().expect("Contract is not initialized"),
+            )),
+        }
+    }
+}
+
+pub(crate) fn write_state_version() {
+    env::storage_write(
+        STATE_VERSION_KEY,
+        &CURRENT_STATE_VERSION.try_to_vec().unwrap(),
+    );
+}
+
+#[near_bindgen]
+impl SlotMachine {
+    //rewrites the stored state in the current layout, called by the account itself
+    //right after deploying a new release. from_v1 is only read when upgrading V1 state
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate(from_v1: Option<V1MigrationArgs>) -> Self {
+        let this = match VersionedSlotMachine::read() {
+            VersionedSlotMachine::V1(old) => {
+                let args = from_v1.expect("from_v1 is required to migrate V1 state");
+                let this = Self::from_v1(old, args);
+                this.internal_sync_nft_supply();
+                this
+            }
+            VersionedSlotMachine::V2(mut this) => {
+                this.config_delay = std::cmp::max(this.config_delay, timelock::MIN_CONFIG_DELAY);
+                *this
+            }
+        };
+        write_state_version();
+        this
+    }
+
+    //adds accounts that held credits before the upgrade from V1 to credit_holders and
+    //counts their credits, accounts without near credits or already indexed are skipped.
+    //Called in pages until get_v1_migration shows every credit indexed. Returns the number of accounts added
+    #[payable]
+    pub fn index_credit_holders(&mut self, account_ids: Vec<AccountId>) -> U64 {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert_one_yocto();
+        let mut added = 0;
+        for account_id in account_ids {
+            if self.internal_index_v1_credits(&account_id) {
+                added += 1;
+            }
+        }
+        U64(added)
+    }
+
+    //ends the upgrade from V1 once the credits of the indexed holders add up to the announced
+    //total. Whatever the contract holds above its liabilities becomes the house bankroll
+    //and the pause state of V1 is restored
+    #[payable]
+    pub fn finish_v1_migration(&mut self) -> PauseStatusView {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert_one_yocto();
+        let migration = self
+            .v1_migration
+            .take()
+            .expect("No migration from V1 in progress");
+        assert!(
+            migration.indexed_credits == migration.total_credits,
+            "Indexed credits add up to {} of the {} announced",
+            migration.indexed_credits,
+            migration.total_credits
+        );
+
+        let available_balance = self.available_balance();
+        assert!(
+            self.total_liabilities() <= available_balance,
+            "Credits and the nft and dev balances add up to more than the contract holds"
+        );
+        self.house_balance = available_balance - self.total_liabilities();
+        if migration.panic_button {
+            self.pause_reason = Some(String::from("Paused before the upgrade"));
+        } else {
+            self.paused = PauseFlags::default();
+            self.pause_reason = None;
+        }
+
+        let status = self.get_pause_status();
+        CoinFlipEvent::PauseUpdate(&status).emit();
+        status
+    }
+
+    pub fn get_v1_migration(&self) -> Option<V1MigrationView> {
+        self.v1_migration.as_ref().map(|migration| V1MigrationView {
+            total_credits: U128(migration.total_credits),
+            indexed_credits: U128(migration.indexed_credits),
+        })
+    }
+}
+
+impl SlotMachine {
+    //V1 kept every credit in the same LookupMap, so credits are carried over as they are
+    //and counted as their holders are indexed. Everything stays paused until
+    //finish_v1_migration, the house bankroll is empty until then
+    fn from_v1(old: SlotMachineV1, args: V1MigrationArgs) -> Self {
+        assert!(
+            env::is_valid_account_id(args.nft_id.as_bytes()),
+            "Invalid nft contract account"
+        );
+        let mut this = Self {
+            owner_id: old.owner_id,
+            nft_id: args.nft_id,
+            credits: old.credits,
+            nft_fee: old.nft_fee,
+            dev_fee: old.dev_fee,
+            house_fee: old.house_fee,
+            win_multiplier: old.win_multiplier,
+            nft_balance: 0,
+            dev_balance: old.dev_balance,
+            max_bet: old.max_bet,
+            min_bet: old.min_bet,
+            min_balance_fraction: old.min_balance_fraction,
+            paused: PauseFlags::all(),
+            pending_bets: LookupMap::new(b"pending_bets".to_vec()),
+            house_balance: 0,
+            total_credits: 0,
+            pending_liabilities: 0,
+            max_bet_bankroll_fraction: 0,
+            nft_mapping_size: 0,
+            nft_reward_per_token: 0,
+            nft_reward_dust: 0,
+            nft_rewards_paid: LookupMap::new(nft_rewards_paid_prefix(0)),
+            nft_epoch: 0,
+            nft_round: None,
+            nft_failed_transfers: UnorderedMap::new(b"nft_failed_transfers".to_vec()),
+            storage_deposits: LookupMap::new(b"storage_deposits".to_vec()),
+            account_storage_usage: 0,
+            total_storage_deposits: 0,
+            ft_assets: UnorderedMap::new(b"ft_assets".to_vec()),
+            ft_credits: LookupMap::new(b"ft_credits".to_vec()),
+            ft_nft_rewards_paid: LookupMap::new(ft_nft_rewards_paid_prefix(0)),
+            min_house_edge: 0,
+            max_house_edge: FRACTIONAL_BASE,
+            config_delay: timelock::MIN_CONFIG_DELAY,
+            config_proposals: UnorderedMap::new(b"config_proposals".to_vec()),
+            next_config_proposal_id: 0,
+            pause_reason: Some(String::from("Migration from V1 in progress")),
+            roles: UnorderedMap::new(b"roles".to_vec()),
+            pending_owner_id: None,
+            staged_upgrade: None,
+            player_stats: LookupMap::new(b"player_stats".to_vec()),
+            bet_history: LookupMap::new(b"bet_history".to_vec()),
+            game_stats: GameStats::default(),
+            credit_holders: UnorderedSet::new(b"credit_holders".to_vec()),
+            v1_migration: Some(V1Migration {
+                total_credits: args.total_credits.0,
+                indexed_credits: 0,
+                panic_button: old.panic_button,
+            }),
+        };
+        //undistributed nft fees are split between the tokens once the nft supply is read
+        this.accrue_nft_fees(old.nft_balance);
+
+        assert!(
+            this.total_liabilities() + args.total_credits.0 <= this.available_balance(),
+            "total_credits and the nft and dev balances add up to more than the contract holds"
+        );
+        this.measure_account_storage_usage();
+        this
+    }
+
+    //counts the near credits of an account that held them before the upgrade from V1 and is
+    //not in credit_holders yet, called before they change. Accounts found after the migration
+    //is finished are paid for by the house bankroll. Returns whether the account was added
+    pub(crate) fn internal_index_v1_credits(&mut self, account_id: &AccountId) -> bool {
+        let credits = self.credits.get(account_id).unwrap_or(0);
+        if credits == 0 || !self.credit_holders.insert(account_id) {
+            return false;
+        }
+        self.total_credits += credits;
+        match self.v1_migration.as_mut() {
+            Some(migration) => {
+                migration.indexed_credits += credits;
+            }
+            None => {
+                self.house_balance = self
+                    .house_balance
+                    .checked_sub(credits)
+                    .expect("House balance cannot cover the credits of the account");
+            }
+        }
+        true
+    }
+}
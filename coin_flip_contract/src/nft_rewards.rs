@@ -0,0 +1,499 @@
+use crate::*;
+
+use crate::events::NftRewardData;
+use near_sdk::{ext_contract, Gas, PromiseResult};
+
+const GAS_FOR_NFT_TOKEN: Gas = 10_000_000_000_000;
+const GAS_FOR_NFT_TOTAL_SUPPLY: Gas = 10_000_000_000_000;
+const GAS_FOR_SUPPLY_CALLBACK: Gas = 30_000_000_000_000;
+const GAS_FOR_CLAIM_CALLBACK: Gas = 20_000_000_000_000;
+const GAS_FOR_NFT_TOKENS: Gas = 30_000_000_000_000;
+const GAS_FOR_DISTRIBUTION_CALLBACK: Gas = 200_000_000_000_000;
+// most tokens paid by a single retrieve_nft_funds call, sized to fit GAS_FOR_DISTRIBUTION_CALLBACK
+const MAX_NFT_DISTRIBUTION_LIMIT: u64 = 50;
+
+// fields of the NEP-171 Token json that are needed to pay its owner
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftToken {
+    pub token_id: TokenId,
+    pub owner_id: AccountId,
+}
+
+// nft contract the nft fees belong to
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftContractView {
+    pub nft_id: AccountId,
+    pub nft_mapping_size: U128,
+    pub nft_epoch: U64,
+}
+
+// payout of the nft fees accrued up to reward_per_token, done in chunks of the
+// nft contract enumeration starting at next_index
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct NftDistributionRound {
+    pub reward_per_token: u128,
+    pub next_index: u128,
+    pub processed: u64,
+    pub total_paid: u128,
+    pub finished: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftDistributionView {
+    pub reward_per_token: U128,
+    pub next_index: U128,
+    pub processed: U64,
+    pub total_paid: U128,
+    pub finished: bool,
+    pub failed_transfers: U64,
+}
+
+// nft payouts whose transfer failed, kept until the owner retries them
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FailedNftTransferView {
+    pub account_id: AccountId,
+    pub amount: U128,
+}
+
+//each nft contract gets its own record of paid tokens
+pub(crate) fn nft_rewards_paid_prefix(nft_epoch: u64) -> Vec<u8> {
+    epoch_prefix(b"nft_rewards_paid", nft_epoch)
+}
+
+pub(crate) fn ft_nft_rewards_paid_prefix(nft_epoch: u64) -> Vec<u8> {
+    epoch_prefix(b"ft_nft_rewards_paid", nft_epoch)
+}
+
+fn epoch_prefix(name: &[u8], nft_epoch: u64) -> Vec<u8> {
+    let mut prefix = name.to_vec();
+    if nft_epoch > 0 {
+        prefix.extend_from_slice(&nft_epoch.to_le_bytes());
+    }
+    prefix
+}
+
+#[ext_contract(ext_nft)]
+pub trait NonFungibleTokenContract {
+    fn nft_token(&self, token_id: TokenId) -> Option<NftToken>;
+    fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<NftToken>;
+    fn nft_total_supply(&self) -> U128;
+}
+
+#[ext_contract(ext_self)]
+pub trait NftRewardsCallbacks {
+    fn on_nft_token_claim(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        nft_id: AccountId,
+        ft_token_id: Option<AccountId>,
+    ) -> U128;
+    fn on_nft_tokens_distribute(&mut self, nft_id: AccountId, from_index: U128, limit: u64)
+        -> U128;
+    fn on_nft_total_supply(&mut self, nft_id: AccountId) -> U128;
+}
+
+#[near_bindgen]
+impl SlotMachine {
+    //token owner claims the nft fees accrued to token_id since its last claim,
+    //in near or in ft_token_id when given. Ownership is checked against the nft contract
+    //before paying
+    pub fn claim_nft_rewards(
+        &mut self,
+        token_id: TokenId,
+        ft_token_id: Option<AccountId>,
+    ) -> Promise {
+        self.assert_not_paused(PausableOperation::Withdrawals);
+        if let Some(ft_token_id) = &ft_token_id {
+            self.internal_ft_asset(ft_token_id);
+        }
+        ext_nft::nft_token(token_id.clone(), &self.nft_id, 0, GAS_FOR_NFT_TOKEN).then(
+            ext_self::on_nft_token_claim(
+                token_id,
+                env::predecessor_account_id(),
+                self.nft_id.clone(),
+                ft_token_id,
+                &env::current_account_id(),
+                0,
+                GAS_FOR_CLAIM_CALLBACK,
+            ),
+        )
+    }
+
+    //reads the number of tokens fees are split between from nft_total_supply of the nft
+    //contract. Anyone can call it after tokens are minted or burned, fees accrued while
+    //the collection was empty are split once it has tokens
+    pub fn sync_nft_supply(&self) -> Promise {
+        self.internal_sync_nft_supply()
+    }
+
+    //snapshots the nft fees accrued so far to be paid out by retrieve_nft_funds
+    #[payable]
+    pub fn start_nft_distribution(&mut self) -> NftDistributionView {
+        self.assert_not_paused(PausableOperation::Withdrawals);
+        self.assert_role(Role::Operator);
+        assert_one_yocto();
+        assert!(
+            self.nft_round.as_ref().is_none_or(|round| round.finished),
+            "Previous nft distribution round must be finished before starting a new one"
+        );
+
+        self.nft_round = Some(NftDistributionRound {
+            reward_per_token: self.nft_reward_per_token,
+            next_index: 0,
+            processed: 0,
+            total_paid: 0,
+            finished: false,
+        });
+        self.get_nft_distribution().unwrap()
+    }
+
+    //pays the next chunk of tokens of the current distribution round,
+    //the round finishes once the nft contract returns less than limit tokens,
+    //limit defaults to and is capped at MAX_NFT_DISTRIBUTION_LIMIT
+    #[payable]
+    pub fn retrieve_nft_funds(&mut self, limit: Option<u64>) -> Promise {
+        self.assert_not_paused(PausableOperation::Withdrawals);
+        self.assert_role(Role::Operator);
+        assert_one_yocto();
+        let from_index = match &self.nft_round {
+            Some(round) if !round.finished => round.next_index,
+            _ => env::panic(b"There is no nft distribution round in progress"),
+        };
+        let limit = std::cmp::min(
+            limit.unwrap_or(MAX_NFT_DISTRIBUTION_LIMIT),
+            MAX_NFT_DISTRIBUTION_LIMIT,
+        );
+
+        ext_nft::nft_tokens(
+            Some(U128(from_index)),
+            Some(limit),
+            &self.nft_id,
+            0,
+            GAS_FOR_NFT_TOKENS,
+        )
+        .then(ext_self::on_nft_tokens_distribute(
+            self.nft_id.clone(),
+            U128(from_index),
+            limit,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_DISTRIBUTION_CALLBACK,
+        ))
+    }
+
+    //sends again the nft payouts that failed to reach account_id
+    #[payable]
+    pub fn retry_nft_transfer(&mut self, account_id: AccountId) -> Promise {
+        self.assert_role(Role::Operator);
+        assert_one_yocto();
+        let amount = self
+            .nft_failed_transfers
+            .remove(&account_id)
+            .expect("No failed nft transfer for this account");
+
+        self.nft_balance -= amount;
+
+        CoinFlipEvent::NftRewardsWithdrawal(vec![NftRewardData {
+            token_id: None,
+            account_id: account_id.clone(),
+            amount: U128(amount),
+            ft_token_id: None,
+        }])
+        .emit();
+        self.checked_transfer(TransferKind::NftRewards, account_id, amount)
+    }
+
+    #[private]
+    pub fn on_nft_token_claim(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        nft_id: AccountId,
+        ft_token_id: Option<AccountId>,
+    ) -> U128 {
+        self.assert_nft_contract_unchanged(&nft_id);
+        let token: Option<NftToken> = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<Option<NftToken>>(&value)
+                    .ok()
+                    .flatten()
+            }
+            _ => None,
+        };
+        assert!(
+            token.map(|token| token.owner_id) == Some(account_id.clone()),
+            "Only the owner of token {} can claim its rewards",
+            token_id
+        );
+
+        let amount = match &ft_token_id {
+            None => self.internal_claim_nft_rewards(&token_id),
+            Some(ft_token_id) => self.internal_claim_ft_nft_rewards(ft_token_id, &token_id),
+        };
+        if amount > 0 {
+            CoinFlipEvent::NftRewardsWithdrawal(vec![NftRewardData {
+                token_id: Some(token_id),
+                account_id: account_id.clone(),
+                amount: U128(amount),
+                ft_token_id: ft_token_id.clone(),
+            }])
+            .emit();
+            self.checked_payout(TransferKind::NftRewards, ft_token_id, account_id, amount);
+        }
+        U128(amount)
+    }
+
+    #[private]
+    pub fn on_nft_tokens_distribute(
+        &mut self,
+        nft_id: AccountId,
+        from_index: U128,
+        limit: u64,
+    ) -> U128 {
+        self.assert_nft_contract_unchanged(&nft_id);
+        let tokens: Vec<NftToken> = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<Vec<NftToken>>(&value)
+                    .expect("Invalid response from nft contract")
+            }
+            _ => env::panic(b"Could not fetch tokens from nft contract"),
+        };
+
+        let payouts = self.internal_distribute_nft_chunk(&tokens, from_index.0, limit);
+        let mut total_amount: u128 = 0;
+        let mut rewards: Vec<NftRewardData> = Vec::new();
+        for (token, amount) in tokens.into_iter().zip(payouts) {
+            if amount > 0 {
+                self.checked_transfer(TransferKind::NftRewards, token.owner_id.clone(), amount);
+                total_amount += amount;
+                rewards.push(NftRewardData {
+                    token_id: Some(token.token_id),
+                    account_id: token.owner_id,
+                    amount: U128(amount),
+                    ft_token_id: None,
+                });
+            }
+        }
+        if !rewards.is_empty() {
+            CoinFlipEvent::NftRewardsWithdrawal(rewards).emit();
+        }
+        U128(total_amount)
+    }
+
+    #[private]
+    pub fn on_nft_total_supply(&mut self, nft_id: AccountId) -> U128 {
+        self.assert_nft_contract_unchanged(&nft_id);
+        let total_supply: U128 = match env::promise_result(0) {
+            PromiseResult::Successful(value) => near_sdk::serde_json::from_slice(&value)
+                .expect("Invalid response from nft contract"),
+            _ => env::panic(b"Could not fetch the total supply from nft contract"),
+        };
+
+        self.nft_mapping_size = total_supply.0;
+        self.accrue_nft_fees(0);
+        let ft_token_ids: Vec<AccountId> = self.ft_assets.keys().collect();
+        for ft_token_id in ft_token_ids {
+            let mut asset = self.internal_ft_asset(&ft_token_id);
+            asset.accrue_nft_fees(0, self.nft_mapping_size);
+            self.ft_assets.insert(&ft_token_id, &asset);
+        }
+
+        CoinFlipEvent::config_update(&[("nft_mapping_size", self.nft_mapping_size.to_string())])
+            .emit();
+        total_supply
+    }
+
+    //nft fees accrued to token_id and not claimed yet, in near or in ft_token_id when given
+    pub fn get_nft_claimable(&self, token_id: TokenId, ft_token_id: Option<AccountId>) -> U128 {
+        match ft_token_id {
+            None => U128(self.nft_claimable(&token_id)),
+            Some(ft_token_id) => U128(self.ft_nft_claimable(&ft_token_id, &token_id)),
+        }
+    }
+
+    pub fn get_nft_distribution(&self) -> Option<NftDistributionView> {
+        self.nft_round.as_ref().map(|round| NftDistributionView {
+            reward_per_token: U128(round.reward_per_token),
+            next_index: U128(round.next_index),
+            processed: U64(round.processed),
+            total_paid: U128(round.total_paid),
+            finished: round.finished,
+            failed_transfers: U64(self.nft_failed_transfers.len()),
+        })
+    }
+
+    pub fn get_nft_failed_transfers(
+        &self,
+        from_index: Option<U64>,
+        limit: Option<u64>,
+    ) -> Vec<FailedNftTransferView> {
+        let keys = self.nft_failed_transfers.keys_as_vector();
+        let values = self.nft_failed_transfers.values_as_vector();
+        let from_index = from_index.map_or(0, |index| index.0);
+        let limit = limit.unwrap_or(keys.len());
+        (from_index..std::cmp::min(from_index.saturating_add(limit), keys.len()))
+            .map(|index| FailedNftTransferView {
+                account_id: keys.get(index).unwrap(),
+                amount: U128(values.get(index).unwrap()),
+            })
+            .collect()
+    }
+
+    //points nft fees to a new collection, fees not claimed by holders of the old
+    //collection are split between the tokens of the new one once its supply is read
+    #[payable]
+    pub fn update_nft_contract(&mut self, nft_id: AccountId) -> Promise {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert_one_yocto();
+        assert!(
+            env::is_valid_account_id(nft_id.as_bytes()),
+            "Invalid nft contract account"
+        );
+
+        self.nft_id = nft_id;
+        self.nft_mapping_size = 0;
+        self.nft_epoch += 1;
+        self.nft_rewards_paid = LookupMap::new(nft_rewards_paid_prefix(self.nft_epoch));
+        self.nft_round = None;
+        self.nft_reward_per_token = 0;
+        self.nft_reward_dust = 0;
+        //failed transfers are still owed to their accounts and are not rolled over
+        let failed_total: u128 = self.nft_failed_transfers.values().sum();
+        let unclaimed = self.nft_balance - failed_total;
+        self.nft_balance = failed_total;
+        self.accrue_nft_fees(unclaimed);
+        self.ft_nft_rewards_paid = LookupMap::new(ft_nft_rewards_paid_prefix(self.nft_epoch));
+        let ft_token_ids: Vec<AccountId> = self.ft_assets.keys().collect();
+        for ft_token_id in ft_token_ids {
+            let mut asset = self.internal_ft_asset(&ft_token_id);
+            let unclaimed = std::mem::replace(&mut asset.nft_balance, 0);
+            asset.nft_reward_per_token = 0;
+            asset.nft_reward_dust = 0;
+            asset.accrue_nft_fees(unclaimed, self.nft_mapping_size);
+            self.ft_assets.insert(&ft_token_id, &asset);
+        }
+
+        CoinFlipEvent::config_update(&[("nft_id", self.nft_id.clone())]).emit();
+        self.internal_sync_nft_supply()
+    }
+
+    pub fn get_nft_contract(&self) -> NftContractView {
+        NftContractView {
+            nft_id: self.nft_id.clone(),
+            nft_mapping_size: U128(self.nft_mapping_size),
+            nft_epoch: U64(self.nft_epoch),
+        }
+    }
+}
+
+impl SlotMachine {
+    //responses from a replaced nft contract must not be paid from the new accrual
+    fn assert_nft_contract_unchanged(&self, nft_id: &AccountId) {
+        assert!(
+            nft_id == &self.nft_id,
+            "nft contract was replaced while the call was in flight"
+        );
+    }
+
+    pub(crate) fn internal_sync_nft_supply(&self) -> Promise {
+        ext_nft::nft_total_supply(&self.nft_id, 0, GAS_FOR_NFT_TOTAL_SUPPLY).then(
+            ext_self::on_nft_total_supply(
+                self.nft_id.clone(),
+                &env::current_account_id(),
+                0,
+                GAS_FOR_SUPPLY_CALLBACK,
+            ),
+        )
+    }
+
+    //splits nft fees evenly over the collection, the remainder is carried to the next accrual.
+    //Fees are carried over entirely while the collection size is unknown or zero
+    pub(crate) fn accrue_nft_fees(&mut self, amount: u128) {
+        self.nft_balance += amount;
+        let to_distribute = amount + self.nft_reward_dust;
+        if self.nft_mapping_size == 0 {
+            self.nft_reward_dust = to_distribute;
+            return;
+        }
+        self.nft_reward_per_token += to_distribute / self.nft_mapping_size;
+        self.nft_reward_dust = to_distribute % self.nft_mapping_size;
+    }
+
+    pub(crate) fn nft_claimable(&self, token_id: &TokenId) -> u128 {
+        self.nft_claimable_up_to(token_id, self.nft_reward_per_token)
+    }
+
+    fn nft_claimable_up_to(&self, token_id: &TokenId, reward_per_token: u128) -> u128 {
+        let paid = self.nft_rewards_paid.get(token_id).unwrap_or(0);
+        std::cmp::min(reward_per_token.saturating_sub(paid), self.nft_balance)
+    }
+
+    //marks token_id as paid up to the current accrual and returns the amount owed
+    pub(crate) fn internal_claim_nft_rewards(&mut self, token_id: &TokenId) -> u128 {
+        self.internal_claim_nft_rewards_up_to(token_id, self.nft_reward_per_token)
+    }
+
+    fn internal_claim_nft_rewards_up_to(
+        &mut self,
+        token_id: &TokenId,
+        reward_per_token: u128,
+    ) -> u128 {
+        let amount = self.nft_claimable_up_to(token_id, reward_per_token);
+        if amount > 0 {
+            self.nft_rewards_paid.insert(token_id, &reward_per_token);
+            self.nft_balance -= amount;
+        }
+        amount
+    }
+
+    //marks a chunk of tokens as paid up to the round snapshot and moves the cursor,
+    //returns the amount owed to each token
+    pub(crate) fn internal_distribute_nft_chunk(
+        &mut self,
+        tokens: &[NftToken],
+        from_index: u128,
+        limit: u64,
+    ) -> Vec<u128> {
+        let reward_per_token = match &self.nft_round {
+            Some(round) if !round.finished => round.reward_per_token,
+            _ => env::panic(b"There is no nft distribution round in progress"),
+        };
+
+        let payouts: Vec<u128> = tokens
+            .iter()
+            .map(|token| self.internal_claim_nft_rewards_up_to(&token.token_id, reward_per_token))
+            .collect();
+
+        let mut round = self.nft_round.take().unwrap();
+        let chunk_end = from_index + tokens.len() as u128;
+        //chunks processed twice pay nothing, the cursor only moves forward
+        if chunk_end > round.next_index {
+            round.processed += (chunk_end - round.next_index) as u64;
+            round.next_index = chunk_end;
+        }
+        round.total_paid += payouts.iter().sum::<u128>();
+        round.finished = (tokens.len() as u64) < limit;
+        self.nft_round = Some(round);
+        payouts
+    }
+
+    //failed payouts stay in nft_balance until retried
+    pub(crate) fn internal_record_failed_nft_transfer(
+        &mut self,
+        account_id: AccountId,
+        amount: u128,
+    ) {
+        let previous = self.nft_failed_transfers.get(&account_id).unwrap_or(0);
+        self.nft_failed_transfers
+            .insert(&account_id, &(previous + amount));
+        self.nft_balance += amount;
+    }
+}
@@ -0,0 +1,177 @@
+use crate::*;
+
+// operations the owner can pause independently of each other
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum PausableOperation {
+    Deposits,
+    Plays,
+    Withdrawals,
+}
+
+impl std::fmt::Display for PausableOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PausableOperation::Deposits => write!(f, "Deposits"),
+            PausableOperation::Plays => write!(f, "Plays"),
+            PausableOperation::Withdrawals => write!(f, "Withdrawals"),
+        }
+    }
+}
+
+// paused operations, fields left out of pause calls are not paused. withdrawals covers
+// nft rewards and dev funds, players can retrieve their credits even while paused
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Debug, Default,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(default)]
+pub struct PauseFlags {
+    pub deposits: bool,
+    pub plays: bool,
+    pub withdrawals: bool,
+}
+
+impl PauseFlags {
+    pub fn all() -> Self {
+        Self {
+            deposits: true,
+            plays: true,
+            withdrawals: true,
+        }
+    }
+
+    pub fn is_paused(&self, operation: PausableOperation) -> bool {
+        match operation {
+            PausableOperation::Deposits => self.deposits,
+            PausableOperation::Plays => self.plays,
+            PausableOperation::Withdrawals => self.withdrawals,
+        }
+    }
+
+    pub fn any(&self) -> bool {
+        self.deposits || self.plays || self.withdrawals
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PauseStatusView {
+    pub flags: PauseFlags,
+    pub reason: Option<String>,
+}
+
+#[near_bindgen]
+impl SlotMachine {
+    //pauses the operations set in flags, all of them when flags is left out.
+    //Operations not set in flags are resumed
+    #[payable]
+    pub fn pause(&mut self, reason: String, flags: Option<PauseFlags>) -> PauseStatusView {
+        self.assert_role(Role::Operator);
+        assert_one_yocto();
+        self.assert_no_v1_migration();
+        let flags = flags.unwrap_or_else(PauseFlags::all);
+        assert!(flags.any(), "At least one operation must be paused");
+
+        self.paused = flags;
+        self.pause_reason = Some(reason);
+
+        let status = self.get_pause_status();
+        CoinFlipEvent::PauseUpdate(&status).emit();
+        status
+    }
+
+    #[payable]
+    pub fn unpause(&mut self) -> PauseStatusView {
+        self.assert_role(Role::Operator);
+        assert_one_yocto();
+        self.assert_no_v1_migration();
+
+        self.paused = PauseFlags::default();
+        self.pause_reason = None;
+
+        let status = self.get_pause_status();
+        CoinFlipEvent::PauseUpdate(&status).emit();
+        status
+    }
+
+    pub fn get_pause_status(&self) -> PauseStatusView {
+        PauseStatusView {
+            flags: self.paused.clone(),
+            reason: self.pause_reason.clone(),
+        }
+    }
+
+    //owner takes funds the contract holds above everything it owes, in near or in
+    //ft_token_id when given. Allowed while paused, the house bankroll shrinks to what is left
+    #[payable]
+    pub fn emergency_withdraw(&mut self, amount: U128, ft_token_id: Option<AccountId>) -> Promise {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert_one_yocto();
+        match &ft_token_id {
+            None => {
+                //credits not indexed yet would be counted as surplus
+                self.assert_no_v1_migration();
+                let surplus = self
+                    .available_balance()
+                    .saturating_sub(self.total_liabilities());
+                assert!(
+                    amount.0 <= surplus,
+                    "Only {} yoctonear are above the contract's liabilities",
+                    surplus
+                );
+                self.house_balance = std::cmp::min(self.house_balance, surplus - amount.0);
+            }
+            Some(ft_token_id) => {
+                let mut asset = self.internal_ft_asset(ft_token_id);
+                let surplus = asset.balance.saturating_sub(asset.total_liabilities());
+                assert!(
+                    amount.0 <= surplus,
+                    "Only {} are above the contract's liabilities",
+                    surplus
+                );
+                asset.house_balance = std::cmp::min(asset.house_balance, surplus - amount.0);
+                self.ft_assets.insert(ft_token_id, &asset);
+            }
+        }
+
+        CoinFlipEvent::EmergencyWithdrawal(AmountData {
+            account_id: self.owner_id.clone(),
+            amount,
+            ft_token_id: ft_token_id.clone(),
+        })
+        .emit();
+        self.checked_payout(
+            TransferKind::EmergencyWithdrawal,
+            ft_token_id,
+            self.owner_id.clone(),
+            amount.0,
+        )
+    }
+}
+
+impl SlotMachine {
+    pub(crate) fn assert_no_v1_migration(&self) {
+        assert!(
+            self.v1_migration.is_none(),
+            "Migration from V1 must be finished first"
+        );
+    }
+
+    pub(crate) fn assert_not_paused(&self, operation: PausableOperation) {
+        if self.paused.is_paused(operation) {
+            env::panic(
+                format!(
+                    "{} are paused by owner: {}",
+                    operation,
+                    self.pause_reason.as_deref().unwrap_or_default()
+                )
+                .as_bytes(),
+            );
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use crate::*;
+
+use crate::events::{OwnershipData, RoleData};
+
+// admin roles the owner can grant to other accounts, the owner holds all of them
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug,
+)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Operator,      // pause, unpause and nft distribution rounds
+    FeeCollector,  // retrieve_dev_funds, paid to the caller
+    ConfigManager, // fees, limits, timelock and token whitelist
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Role::Operator => write!(f, "operator"),
+            Role::FeeCollector => write!(f, "fee_collector"),
+            Role::ConfigManager => write!(f, "config_manager"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoleHolderView {
+    pub account_id: AccountId,
+    pub roles: Vec<Role>,
+}
+
+#[near_bindgen]
+impl SlotMachine {
+    #[payable]
+    pub fn grant_role(&mut self, account_id: AccountId, role: Role) {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert_one_yocto();
+        assert!(
+            env::is_valid_account_id(account_id.as_bytes()),
+            "Invalid account"
+        );
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        assert!(
+            !roles.contains(&role),
+            "{} already has the {} role",
+            account_id,
+            role
+        );
+
+        roles.push(role);
+        self.roles.insert(&account_id, &roles);
+
+        CoinFlipEvent::RoleGranted(RoleData { account_id, role }).emit();
+    }
+
+    #[payable]
+    pub fn revoke_role(&mut self, account_id: AccountId, role: Role) {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert_one_yocto();
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        assert!(
+            roles.contains(&role),
+            "{} does not have the {} role",
+            account_id,
+            role
+        );
+
+        roles.retain(|held| *held != role);
+        if roles.is_empty() {
+            self.roles.remove(&account_id);
+        } else {
+            self.roles.insert(&account_id, &roles);
+        }
+
+        CoinFlipEvent::RoleRevoked(RoleData { account_id, role }).emit();
+    }
+
+    //roles granted to account_id, the owner is not listed but holds every role
+    pub fn get_roles(&self, account_id: AccountId) -> Vec<Role> {
+        self.roles.get(&account_id).unwrap_or_default()
+    }
+
+    pub fn get_role_holders(&self) -> Vec<RoleHolderView> {
+        self.roles
+            .iter()
+            .map(|(account_id, roles)| RoleHolderView { account_id, roles })
+            .collect()
+    }
+
+    //first step of an ownership transfer, the new owner must call accept_ownership.
+    //A new proposal replaces the previous one
+    #[payable]
+    pub fn propose_owner(&mut self, new_owner_id: AccountId) {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert_one_yocto();
+        assert!(
+            env::is_valid_account_id(new_owner_id.as_bytes()),
+            "Invalid owner account"
+        );
+        assert!(
+            new_owner_id != self.owner_id,
+            "{} is already the owner",
+            new_owner_id
+        );
+
+        self.pending_owner_id = Some(new_owner_id.clone());
+
+        CoinFlipEvent::OwnershipProposed(OwnershipData {
+            old_owner_id: self.owner_id.clone(),
+            new_owner_id,
+        })
+        .emit();
+    }
+
+    #[payable]
+    pub fn accept_ownership(&mut self) {
+        assert_one_yocto();
+        let new_owner_id = env::predecessor_account_id();
+        assert!(
+            self.pending_owner_id.as_ref() == Some(&new_owner_id),
+            "Only the proposed owner can accept ownership"
+        );
+
+        let old_owner_id = std::mem::replace(&mut self.owner_id, new_owner_id.clone());
+        self.pending_owner_id = None;
+
+        CoinFlipEvent::OwnershipTransferred(OwnershipData {
+            old_owner_id,
+            new_owner_id,
+        })
+        .emit();
+    }
+
+    #[payable]
+    pub fn cancel_owner_proposal(&mut self) {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert_one_yocto();
+        let new_owner_id = self
+            .pending_owner_id
+            .take()
+            .expect("There is no ownership proposal");
+
+        CoinFlipEvent::OwnershipProposalCanceled(OwnershipData {
+            old_owner_id: self.owner_id.clone(),
+            new_owner_id,
+        })
+        .emit();
+    }
+
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner_id.clone()
+    }
+}
+
+impl SlotMachine {
+    pub(crate) fn has_role(&self, account_id: &AccountId, role: Role) -> bool {
+        *account_id == self.owner_id
+            || self
+                .roles
+                .get(account_id)
+                .unwrap_or_default()
+                .contains(&role)
+    }
+
+    //guard of admin methods the owner can delegate
+    pub(crate) fn assert_role(&self, role: Role) {
+        assert!(
+            self.has_role(&env::predecessor_account_id(), role),
+            "Only owner or {} can call this function",
+            role
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use crate::*;
+
+use crate::events::GameStatsData;
+use near_sdk::json_types::I64;
+
+// resolved bets kept in each account's history, older ones are overwritten
+pub const BET_HISTORY_SIZE: usize = 20;
+// most bets returned by a single get_bet_history call
+const MAX_BET_HISTORY_LIMIT: u64 = BET_HISTORY_SIZE as u64;
+
+// aggregate results of an account's resolved bets in near or in a single token,
+// amounts are in that token. Refunded bets are not counted
+#[derive(BorshDeserialize, BorshSerialize, Default, Clone)]
+pub struct PlayerStats {
+    pub total_wagered: u128,
+    pub total_won: u128, // sum of the payouts of won bets
+    pub bets: u64,
+    pub wins: u64,
+    pub biggest_win: u128,
+    pub current_streak: i64, // consecutive wins when positive, consecutive losses when negative
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PlayerStatsView {
+    pub total_wagered: U128,
+    pub total_won: U128,
+    pub bets: U64,
+    pub wins: U64,
+    pub biggest_win: U128,
+    pub current_streak: I64,
+}
+
+impl PlayerStats {
+    pub fn record(&mut self, bet_size: u128, won: bool, payout: u128) {
+        self.total_wagered += bet_size;
+        self.total_won += payout;
+        self.bets += 1;
+        if won {
+            self.wins += 1;
+            self.biggest_win = std::cmp::max(self.biggest_win, payout);
+            self.current_streak = std::cmp::max(self.current_streak, 0) + 1;
+        } else {
+            self.current_streak = std::cmp::min(self.current_streak, 0) - 1;
+        }
+    }
+
+    pub fn view(&self) -> PlayerStatsView {
+        PlayerStatsView {
+            total_wagered: U128(self.total_wagered),
+            total_won: U128(self.total_won),
+            bets: U64(self.bets),
+            wins: U64(self.wins),
+            biggest_win: U128(self.biggest_win),
+            current_streak: I64(self.current_streak),
+        }
+    }
+}
+
+// totals of all resolved bets in near or in a single token, amounts are in that token
+#[derive(BorshDeserialize, BorshSerialize, Default, Clone)]
+pub struct GameStats {
+    pub total_bets: u64,
+    pub total_volume: u128,  // sum of bet sizes
+    pub total_payouts: u128, // sum of the payouts of won bets
+    pub nft_fees: u128,
+    pub dev_fees: u128,
+    pub house_fees: u128,
+    pub unique_players: u64, // accounts that resolved a bet, counted again if they re-register
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GameStatsView {
+    pub total_bets: U64,
+    pub total_volume: U128,
+    pub total_payouts: U128,
+    pub nft_fees: U128,
+    pub dev_fees: U128,
+    pub house_fees: U128,
+    pub unique_players: U64,
+}
+
+impl GameStats {
+    pub fn record(&mut self, pending_bet: &PendingBet, payout: u128, new_player: bool) {
+        self.total_bets += 1;
+        self.total_volume += pending_bet.bet_size;
+        self.total_payouts += payout;
+        self.nft_fees += pending_bet.nft_cut;
+        self.dev_fees += pending_bet.dev_cut;
+        self.house_fees += pending_bet.house_cut;
+        if new_player {
+            self.unique_players += 1;
+        }
+    }
+
+    pub fn view(&self) -> GameStatsView {
+        GameStatsView {
+            total_bets: U64(self.total_bets),
+            total_volume: U128(self.total_volume),
+            total_payouts: U128(self.total_payouts),
+            nft_fees: U128(self.nft_fees),
+            dev_fees: U128(self.dev_fees),
+            house_fees: U128(self.house_fees),
+            unique_players: U64(self.unique_players),
+        }
+    }
+}
+
+// resolved bet as kept in the bet history
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct BetRecord {
+    pub bet_type: bool,
+    pub won: bool,
+    pub bet_size: u128,
+    pub payout: u128,
+    pub resolve_block: u64,
+    pub ft_token_id: Option<AccountId>, // None for bets in near
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BetRecordView {
+    pub chosen_side: CoinSide,
+    pub won: bool,
+    pub bet_size: U128,
+    pub payout: U128,
+    pub resolve_block: U64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ft_token_id: Option<AccountId>,
+}
+
+impl BetRecord {
+    pub fn view(&self) -> BetRecordView {
+        BetRecordView {
+            chosen_side: CoinSide::from(self.bet_type),
+            won: self.won,
+            bet_size: U128(self.bet_size),
+            payout: U128(self.payout),
+            resolve_block: U64(self.resolve_block),
+            ft_token_id: self.ft_token_id.clone(),
+        }
+    }
+}
+
+// ring buffer of an account's last BET_HISTORY_SIZE resolved bets,
+// next is the slot the following bet is written to
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct BetHistory {
+    pub records: Vec<BetRecord>,
+    pub next: u32,
+}
+
+impl BetHistory {
+    pub fn push(&mut self, record: BetRecord) {
+        if self.records.len() < BET_HISTORY_SIZE {
+            self.records.push(record);
+        } else {
+            self.records[self.next as usize] = record;
+        }
+        self.next = ((self.next as usize + 1) % BET_HISTORY_SIZE) as u32;
+    }
+
+    //index 0 is the most recent bet
+    pub fn get(&self, index: usize) -> Option<&BetRecord> {
+        let len = self.records.len();
+        if index >= len {
+            return None;
+        }
+        self.records
+            .get((self.next as usize + 2 * len - 1 - index) % len)
+    }
+}
+
+#[near_bindgen]
+impl SlotMachine {
+    //stats of the bets of account_id in near, or in ft_token_id when given
+    pub fn get_player_stats(
+        &self,
+        account_id: AccountId,
+        ft_token_id: Option<AccountId>,
+    ) -> PlayerStatsView {
+        self.player_stats
+            .get(&(account_id, ft_token_id))
+            .unwrap_or_default()
+            .view()
+    }
+
+    //totals of all bets in near, or in ft_token_id when given
+    pub fn get_game_stats(&self, ft_token_id: Option<AccountId>) -> GameStatsView {
+        match ft_token_id {
+            None => self.game_stats.view(),
+            Some(ft_token_id) => self.internal_ft_asset(&ft_token_id).game_stats.view(),
+        }
+    }
+
+    //last resolved bets of account_id in all tokens, most recent first
+    pub fn get_bet_history(
+        &self,
+        account_id: AccountId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<BetRecordView> {
+        let history = self.bet_history.get(&account_id).unwrap_or_default();
+        let from_index = from_index.unwrap_or(0) as usize;
+        let limit = std::cmp::min(
+            limit.unwrap_or(MAX_BET_HISTORY_LIMIT),
+            MAX_BET_HISTORY_LIMIT,
+        ) as usize;
+        (from_index..from_index.saturating_add(limit))
+            .filter_map(|index| history.get(index))
+            .map(|record| record.view())
+            .collect()
+    }
+}
+
+impl SlotMachine {
+    pub(crate) fn internal_record_bet(
+        &mut self,
+        account_id: &AccountId,
+        pending_bet: &PendingBet,
+        won: bool,
+        payout: u128,
+    ) {
+        let stats_key = (account_id.clone(), pending_bet.ft_token_id.clone());
+        let mut stats = self.player_stats.get(&stats_key).unwrap_or_default();
+        //accounts have no stats before their first bet or after unregistering
+        let new_player = stats.bets == 0;
+        stats.record(pending_bet.bet_size, won, payout);
+        self.player_stats.insert(&stats_key, &stats);
+
+        let game_stats = match &pending_bet.ft_token_id {
+            None => {
+                self.game_stats.record(pending_bet, payout, new_player);
+                self.game_stats.view()
+            }
+            Some(ft_token_id) => {
+                let mut asset = self.internal_ft_asset(ft_token_id);
+                asset.game_stats.record(pending_bet, payout, new_player);
+                self.ft_assets.insert(ft_token_id, &asset);
+                asset.game_stats.view()
+            }
+        };
+        CoinFlipEvent::GameStatsUpdate(GameStatsData {
+            stats: &game_stats,
+            ft_token_id: pending_bet.ft_token_id.clone(),
+        })
+        .emit();
+
+        let mut history = self.bet_history.get(account_id).unwrap_or_default();
+        history.push(BetRecord {
+            bet_type: pending_bet.bet_type,
+            won,
+            bet_size: pending_bet.bet_size,
+            payout,
+            resolve_block: env::block_index(),
+            ft_token_id: pending_bet.ft_token_id.clone(),
+        });
+        self.bet_history.insert(account_id, &history);
+    }
+
+    //drops the stats and history of an account that unregisters,
+    //nothing is kept so it is counted again in unique_players if it comes back
+    pub(crate) fn internal_remove_player_records(&mut self, account_id: &AccountId) {
+        self.player_stats.remove(&(account_id.clone(), None));
+        for ft_token_id in self.ft_assets.keys() {
+            self.player_stats
+                .remove(&(account_id.clone(), Some(ft_token_id)));
+        }
+        self.bet_history.remove(account_id);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::*;
+
+use crate::fungible_tokens::MAX_FT_TOKENS;
+use crate::stats::{BetHistory, BetRecord, PlayerStats, BET_HISTORY_SIZE};
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+use near_sdk::json_types::ValidAccountId;
+
+#[near_bindgen]
+impl StorageManagement for SlotMachine {
+    //registers account_id, or the caller, paying for its entries in state
+    //the storage balance is fixed so any deposit above the minimum is refunded
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<ValidAccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        //registration_only makes no difference, the storage balance is fixed
+        let _ = registration_only;
+        let amount = env::attached_deposit();
+        let account_id: AccountId = account_id
+            .map(|account_id| account_id.into())
+            .unwrap_or_else(env::predecessor_account_id);
+        let min_balance = self.storage_balance_bounds().min.0;
+
+        let refund = if self.storage_deposits.contains_key(&account_id) {
+            amount
+        } else {
+            assert!(
+                amount >= min_balance,
+                "The attached deposit is less than the minimum storage balance of {} yoctonear",
+                min_balance
+            );
+            self.storage_deposits.insert(&account_id, &min_balance);
+            self.total_storage_deposits += min_balance;
+            amount - min_balance
+        };
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        self.internal_storage_balance_of(&account_id).unwrap()
+    }
+
+    //nothing is ever available to withdraw, the whole storage balance is locked while registered
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let storage_balance = self
+            .internal_storage_balance_of(&account_id)
+            .expect("Account is not registered");
+        assert!(
+            amount.is_none_or(|amount| amount.0 == 0),
+            "The amount is greater than the available storage balance"
+        );
+        storage_balance
+    }
+
+    //unregisters the caller, sending back its storage balance and,
+    //with force, any remaining credits
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let storage_balance = match self.storage_deposits.get(&account_id) {
+            Some(storage_balance) => storage_balance,
+            None => return false,
+        };
+        assert!(
+            force.unwrap_or(false) || self.credits.get(&account_id).unwrap_or(0) == 0,
+            "Can't unregister an account with credits without force"
+        );
+        assert!(
+            self.pending_bets.get(&account_id).is_none(),
+            "Pending bet must be resolved or refunded before unregistering"
+        );
+        for ft_token_id in self.ft_assets.keys() {
+            assert!(
+                self.internal_credits(&account_id, Some(&ft_token_id)) == 0,
+                "Credits in {} must be retrieved before unregistering",
+                ft_token_id
+            );
+        }
+
+        self.internal_remove_player_records(&account_id);
+        self.internal_index_v1_credits(&account_id);
+        let credits = self.credits.remove(&account_id).unwrap_or(0);
+        self.credit_holders.remove(&account_id);
+        self.total_credits -= credits;
+        self.storage_deposits.remove(&account_id);
+        self.total_storage_deposits -= storage_balance;
+
+        Promise::new(account_id.clone()).transfer(storage_balance);
+        if credits > 0 {
+            CoinFlipEvent::Withdrawal(AmountData {
+                account_id: account_id.clone(),
+                amount: U128(credits),
+                ft_token_id: None,
+            })
+            .emit();
+            self.checked_transfer(TransferKind::Credits, account_id, credits);
+        }
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        let required = Balance::from(self.account_storage_usage) * env::storage_byte_cost();
+        StorageBalanceBounds {
+            min: U128(required),
+            max: Some(U128(required)),
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: ValidAccountId) -> Option<StorageBalance> {
+        self.internal_storage_balance_of(account_id.as_ref())
+    }
+}
+
+impl SlotMachine {
+    //bytes of state a registered account can hold, measured with the longest account ids,
+    //credits and stats in a single token and a full bet history are covered
+    pub(crate) fn measure_account_storage_usage(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id: AccountId = "a".repeat(64);
+        //an account can hold credits and stats in every token that can be whitelisted
+        let tmp_ft_token_ids: Vec<AccountId> = (0..MAX_FT_TOKENS)
+            .map(|index| format!("{:b>64}", index))
+            .collect();
+        self.storage_deposits.insert(&tmp_account_id, &0);
+        self.credits.insert(&tmp_account_id, &0);
+        self.credit_holders.insert(&tmp_account_id);
+        self.player_stats
+            .insert(&(tmp_account_id.clone(), None), &PlayerStats::default());
+        for ft_token_id in tmp_ft_token_ids.iter() {
+            self.ft_credits
+                .insert(&(tmp_account_id.clone(), ft_token_id.clone()), &0);
+            self.player_stats.insert(
+                &(tmp_account_id.clone(), Some(ft_token_id.clone())),
+                &PlayerStats::default(),
+            );
+        }
+        self.pending_bets.insert(
+            &tmp_account_id,
+            &PendingBet {
+                bet_type: true,
+                bet_size: 0,
+                nft_cut: 0,
+                dev_cut: 0,
+                house_cut: 0,
+                win_payout: 0,
+                commit_block: 0,
+                ft_token_id: Some(tmp_ft_token_ids[0].clone()),
+            },
+        );
+        let mut tmp_history = BetHistory::default();
+        for _ in 0..BET_HISTORY_SIZE {
+            tmp_history.push(BetRecord {
+                bet_type: true,
+                won: true,
+                bet_size: 0,
+                payout: 0,
+                resolve_block: 0,
+                ft_token_id: Some(tmp_ft_token_ids[0].clone()),
+            });
+        }
+        self.bet_history.insert(&tmp_account_id, &tmp_history);
+        self.account_storage_usage = env::storage_usage() - initial_storage_usage;
+        self.storage_deposits.remove(&tmp_account_id);
+        self.credits.remove(&tmp_account_id);
+        self.credit_holders.remove(&tmp_account_id);
+        self.player_stats.remove(&(tmp_account_id.clone(), None));
+        for ft_token_id in tmp_ft_token_ids {
+            self.ft_credits
+                .remove(&(tmp_account_id.clone(), ft_token_id.clone()));
+            self.player_stats
+                .remove(&(tmp_account_id.clone(), Some(ft_token_id)));
+        }
+        self.pending_bets.remove(&tmp_account_id);
+        self.bet_history.remove(&tmp_account_id);
+    }
+
+    fn internal_storage_balance_of(&self, account_id: &AccountId) -> Option<StorageBalance> {
+        self.storage_deposits
+            .get(account_id)
+            .map(|total| StorageBalance {
+                total: U128(total),
+                available: U128(0),
+            })
+    }
+
+    pub(crate) fn assert_registered(&self, account_id: &AccountId) {
+        assert!(
+            self.storage_deposits.contains_key(account_id),
+            "Account {} is not registered, call storage_deposit first",
+            account_id
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::*;
+
+use crate::config::ConfigPatch;
+use crate::fungible_tokens::FtTokenConfig;
+
+// shortest config_delay a contract can have, 1 day in nanoseconds
+pub const MIN_CONFIG_DELAY: u64 = 86_400_000_000_000;
+
+// config change waiting for its effective time, config_delay is only set
+// when the proposal lowers the delay itself and ft_token when it changes token settings
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ConfigProposal {
+    pub patch: ConfigPatch,
+    pub ft_token: Option<FtTokenConfig>,
+    pub config_delay: Option<u64>,
+    pub effective_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConfigProposalView {
+    pub id: U64,
+    pub patch: ConfigPatch,
+    pub ft_token: Option<FtTokenConfig>,
+    pub config_delay: Option<U64>,
+    pub effective_at: U64,
+}
+
+impl ConfigProposal {
+    pub fn view(&self, id: u64) -> ConfigProposalView {
+        ConfigProposalView {
+            id: U64(id),
+            patch: self.patch.clone(),
+            ft_token: self.ft_token.clone(),
+            config_delay: self.config_delay.map(U64),
+            effective_at: U64(self.effective_at),
+        }
+    }
+}
+
+#[near_bindgen]
+impl SlotMachine {
+    //schedules a config change, effective_at (nanoseconds) defaults to and can't be
+    //earlier than config_delay from now. ft_token whitelists a token or updates its settings
+    #[payable]
+    pub fn propose_config(
+        &mut self,
+        patch: ConfigPatch,
+        ft_token: Option<FtTokenConfig>,
+        config_delay: Option<U64>,
+        effective_at: Option<U64>,
+    ) -> ConfigProposalView {
+        self.assert_role(Role::ConfigManager);
+        assert_one_yocto();
+        let earliest = env::block_timestamp() + self.config_delay;
+        let effective_at = effective_at.map_or(earliest, |effective_at| effective_at.0);
+        assert!(
+            effective_at >= earliest,
+            "Proposal can't take effect before {}",
+            earliest
+        );
+        if let Some(config_delay) = &config_delay {
+            assert_min_config_delay(config_delay.0);
+        }
+        if let Some(ft_token) = &ft_token {
+            assert!(
+                env::is_valid_account_id(ft_token.ft_token_id.as_bytes()),
+                "Invalid token contract account"
+            );
+        }
+
+        let id = self.next_config_proposal_id;
+        self.next_config_proposal_id += 1;
+        let proposal = ConfigProposal {
+            patch,
+            ft_token,
+            config_delay: config_delay.map(|config_delay| config_delay.0),
+            effective_at,
+        };
+        self.config_proposals.insert(&id, &proposal);
+
+        let view = proposal.view(id);
+        CoinFlipEvent::ConfigProposed(&view).emit();
+        view
+    }
+
+    //applies a proposal once its effective time is reached, can be called by anyone
+    pub fn execute_config(&mut self, id: U64) -> ContractConfig {
+        let proposal = self
+            .config_proposals
+            .remove(&id.0)
+            .expect("No config proposal with this id");
+        assert!(
+            env::block_timestamp() >= proposal.effective_at,
+            "Proposal can only be executed from {}",
+            proposal.effective_at
+        );
+
+        self.internal_apply_config_patch(&proposal.patch);
+        let mut fields = proposal.patch.fields();
+        if let Some(config_delay) = proposal.config_delay {
+            assert_min_config_delay(config_delay);
+            self.config_delay = config_delay;
+            fields.push(("config_delay", config_delay.to_string()));
+        }
+        if !fields.is_empty() {
+            CoinFlipEvent::config_update(&fields).emit();
+        }
+        if let Some(ft_token) = &proposal.ft_token {
+            self.internal_set_ft_token(ft_token);
+        }
+        self.get_config()
+    }
+
+    #[payable]
+    pub fn cancel_config(&mut self, id: U64) {
+        self.assert_role(Role::ConfigManager);
+        assert_one_yocto();
+        let proposal = self
+            .config_proposals
+            .remove(&id.0)
+            .expect("No config proposal with this id");
+
+        CoinFlipEvent::ConfigProposalCanceled(&proposal.view(id.0)).emit();
+    }
+
+    //raises the minimum delay of config proposals right away,
+    //lowering it must itself be proposed
+    #[payable]
+    pub fn update_config_delay(&mut self, config_delay: U64) {
+        self.assert_role(Role::ConfigManager);
+        assert_one_yocto();
+        assert!(
+            config_delay.0 >= self.config_delay,
+            "config_delay can only be lowered through propose_config"
+        );
+
+        self.config_delay = config_delay.0;
+
+        CoinFlipEvent::config_update(&[("config_delay", self.config_delay.to_string())]).emit();
+    }
+
+    pub fn get_config_delay(&self) -> U64 {
+        U64(self.config_delay)
+    }
+
+    //upcoming config changes, in the order they were proposed
+    pub fn get_config_proposals(&self) -> Vec<ConfigProposalView> {
+        let mut proposals: Vec<ConfigProposalView> = self
+            .config_proposals
+            .iter()
+            .map(|(id, proposal)| proposal.view(id))
+            .collect();
+        proposals.sort_by_key(|proposal| proposal.id.0);
+        proposals
+    }
+}
+
+impl SlotMachine {
+    //settings can only change right away while no delay is set
+    pub(crate) fn assert_no_config_delay(&self) {
+        assert!(
+            self.config_delay == 0,
+            "Config changes must go through propose_config while config_delay is set"
+        );
+    }
+}
+
+fn assert_min_config_delay(config_delay: u64) {
+    assert!(
+        config_delay >= MIN_CONFIG_DELAY,
+        "config_delay can't be lower than {}",
+        MIN_CONFIG_DELAY
+    );
+}
@@ -0,0 +1,188 @@
+use crate::*;
+
+use crate::events::TransferData;
+use near_sdk::{ext_contract, Gas, PromiseResult};
+
+const GAS_FOR_TRANSFER_CALLBACK: Gas = 5_000_000_000_000;
+const GAS_FOR_FT_TRANSFER: Gas = 10_000_000_000_000;
+
+// balance an outgoing transfer was taken from, restored if the transfer fails.
+// Emergency withdrawals are only taken from the surplus, which the failed amount rejoins
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum TransferKind {
+    Credits,
+    DevFunds,
+    HouseFunds,
+    NftRewards,
+    EmergencyWithdrawal,
+}
+
+#[ext_contract(ext_transfers)]
+pub trait TransferCallbacks {
+    fn on_transfer(&mut self, kind: TransferKind, account_id: AccountId, amount: U128) -> bool;
+    fn on_ft_transfer(
+        &mut self,
+        kind: TransferKind,
+        ft_token_id: AccountId,
+        account_id: AccountId,
+        amount: U128,
+    ) -> bool;
+}
+
+#[ext_contract(ext_ft)]
+pub trait FungibleTokenContract {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+#[near_bindgen]
+impl SlotMachine {
+    #[private]
+    pub fn on_transfer(&mut self, kind: TransferKind, account_id: AccountId, amount: U128) -> bool {
+        let success = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if !success {
+            self.internal_restore_transfer(kind, account_id, amount.0);
+        }
+        success
+    }
+
+    #[private]
+    pub fn on_ft_transfer(
+        &mut self,
+        kind: TransferKind,
+        ft_token_id: AccountId,
+        account_id: AccountId,
+        amount: U128,
+    ) -> bool {
+        let success = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if !success {
+            self.internal_restore_ft_transfer(kind, ft_token_id, account_id, amount.0);
+        }
+        success
+    }
+}
+
+impl SlotMachine {
+    //sends amount to account_id, the amount is given back to its balance if the transfer fails
+    pub(crate) fn checked_transfer(
+        &self,
+        kind: TransferKind,
+        account_id: AccountId,
+        amount: u128,
+    ) -> Promise {
+        Promise::new(account_id.clone())
+            .transfer(amount)
+            .then(ext_transfers::on_transfer(
+                kind,
+                account_id,
+                U128(amount),
+                &env::current_account_id(),
+                0,
+                GAS_FOR_TRANSFER_CALLBACK,
+            ))
+    }
+
+    //same as checked_transfer for an amount of ft_token_id, sent with ft_transfer
+    pub(crate) fn checked_ft_transfer(
+        &mut self,
+        kind: TransferKind,
+        ft_token_id: AccountId,
+        account_id: AccountId,
+        amount: u128,
+    ) -> Promise {
+        assert!(amount > 0, "There is nothing to transfer");
+        let mut asset = self.internal_ft_asset(&ft_token_id);
+        asset.balance -= amount;
+        self.ft_assets.insert(&ft_token_id, &asset);
+
+        ext_ft::ft_transfer(
+            account_id.clone(),
+            U128(amount),
+            None,
+            &ft_token_id,
+            1,
+            GAS_FOR_FT_TRANSFER,
+        )
+        .then(ext_transfers::on_ft_transfer(
+            kind,
+            ft_token_id,
+            account_id,
+            U128(amount),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_TRANSFER_CALLBACK,
+        ))
+    }
+
+    //pays out in near when ft_token_id is None, in the token otherwise
+    pub(crate) fn checked_payout(
+        &mut self,
+        kind: TransferKind,
+        ft_token_id: Option<AccountId>,
+        account_id: AccountId,
+        amount: u128,
+    ) -> Promise {
+        match ft_token_id {
+            None => self.checked_transfer(kind, account_id, amount),
+            Some(ft_token_id) => self.checked_ft_transfer(kind, ft_token_id, account_id, amount),
+        }
+    }
+
+    pub(crate) fn internal_restore_transfer(
+        &mut self,
+        kind: TransferKind,
+        account_id: AccountId,
+        amount: u128,
+    ) {
+        match kind {
+            TransferKind::Credits => {
+                self.internal_add_credits(&account_id, None, amount);
+            }
+            TransferKind::DevFunds => self.dev_balance += amount,
+            TransferKind::HouseFunds => self.house_balance += amount,
+            TransferKind::NftRewards => {
+                self.internal_record_failed_nft_transfer(account_id.clone(), amount)
+            }
+            TransferKind::EmergencyWithdrawal => {}
+        }
+        CoinFlipEvent::TransferFailed(TransferData {
+            kind,
+            account_id,
+            amount: U128(amount),
+            ft_token_id: None,
+        })
+        .emit();
+    }
+
+    //nft rewards paid in a token that fail to arrive become credits of the holder
+    pub(crate) fn internal_restore_ft_transfer(
+        &mut self,
+        kind: TransferKind,
+        ft_token_id: AccountId,
+        account_id: AccountId,
+        amount: u128,
+    ) {
+        let mut asset = self.internal_ft_asset(&ft_token_id);
+        asset.balance += amount;
+        match kind {
+            TransferKind::DevFunds => asset.dev_balance += amount,
+            TransferKind::HouseFunds => asset.house_balance += amount,
+            TransferKind::Credits
+            | TransferKind::NftRewards
+            | TransferKind::EmergencyWithdrawal => {}
+        }
+        self.ft_assets.insert(&ft_token_id, &asset);
+        if kind == TransferKind::Credits || kind == TransferKind::NftRewards {
+            self.internal_add_credits(&account_id, Some(&ft_token_id), amount);
+        }
+
+        CoinFlipEvent::TransferFailed(TransferData {
+            kind,
+            account_id,
+            amount: U128(amount),
+            ft_token_id: Some(ft_token_id),
+        })
+        .emit();
+    }
+}
@@ -0,0 +1,198 @@
+use crate::*;
+
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{ext_contract, Gas, PromiseResult};
+
+// storage key of the staged wasm, kept out of SlotMachine so it is not loaded on every call
+const STAGED_CODE_KEY: &[u8] = b"staged_code";
+// gas kept by upgrade itself, the rest is given to migrate
+const GAS_FOR_UPGRADE: Gas = 30_000_000_000_000;
+const GAS_FOR_UPGRADE_CALLBACK: Gas = 20_000_000_000_000;
+
+// wasm staged by the owner, executable_at is config_delay after staging
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StagedUpgrade {
+    pub code_hash: Vec<u8>,
+    pub executable_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StagedUpgradeView {
+    pub code_hash: String, // hex sha256 of the wasm
+    pub executable_at: U64,
+}
+
+impl StagedUpgrade {
+    pub fn view(&self) -> StagedUpgradeView {
+        StagedUpgradeView {
+            code_hash: hex_hash(&self.code_hash),
+            executable_at: U64(self.executable_at),
+        }
+    }
+}
+
+// on_upgrade runs on the code deployed by upgrade, or on the previous code if
+// migrate failed, so every release must keep it
+#[ext_contract(ext_upgrade)]
+pub trait UpgradeCallbacks {
+    fn on_upgrade(&mut self, code_hash: String) -> bool;
+}
+
+#[near_bindgen]
+impl SlotMachine {
+    //stores the wasm of a new release, the attached deposit must cover its storage.
+    //Staging again replaces the previous code and restarts the waiting period, storage
+    //released by replacing larger code is refunded with the excess deposit
+    #[payable]
+    pub fn stage_upgrade(&mut self, code: Base64VecU8) -> StagedUpgradeView {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert!(!code.0.is_empty(), "code must not be empty");
+
+        let initial_storage_usage = env::storage_usage();
+        env::storage_write(STAGED_CODE_KEY, &code.0);
+        let final_storage_usage = env::storage_usage();
+        let storage_cost = final_storage_usage.saturating_sub(initial_storage_usage) as u128
+            * env::storage_byte_cost();
+        let released = initial_storage_usage.saturating_sub(final_storage_usage) as u128
+            * env::storage_byte_cost();
+        let required_deposit = std::cmp::max(storage_cost, 1);
+        assert!(
+            env::attached_deposit() >= required_deposit,
+            "Attached deposit must cover the {} yoctonear of storage of the code",
+            required_deposit
+        );
+
+        let staged_upgrade = StagedUpgrade {
+            code_hash: env::sha256(&code.0),
+            executable_at: env::block_timestamp() + self.config_delay,
+        };
+        let view = staged_upgrade.view();
+        self.staged_upgrade = Some(staged_upgrade);
+
+        CoinFlipEvent::UpgradeStaged(&view).emit();
+        let refund = env::attached_deposit() - required_deposit + released;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        view
+    }
+
+    //drops the staged code and returns the storage it used to the owner
+    #[payable]
+    pub fn cancel_upgrade(&mut self) -> Promise {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert_one_yocto();
+        let staged_upgrade = self
+            .staged_upgrade
+            .take()
+            .expect("There is no staged upgrade");
+
+        CoinFlipEvent::UpgradeCanceled(&staged_upgrade.view()).emit();
+        self.internal_remove_staged_code()
+    }
+
+    //deploys the staged code to this account and calls migrate on it in the same batch,
+    //if migrate fails the whole upgrade is reverted. code_hash must match the staged code.
+    //The code stays staged until on_upgrade confirms the deployment, so a failed upgrade
+    //can be executed again or canceled
+    #[payable]
+    pub fn upgrade(&mut self, code_hash: String, migrate_args: Option<String>) -> Promise {
+        assert!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only owner can call this function"
+        );
+        assert_one_yocto();
+        let staged_upgrade = self
+            .staged_upgrade
+            .as_ref()
+            .expect("There is no staged upgrade");
+        assert!(
+            hex_hash(&staged_upgrade.code_hash) == code_hash,
+            "code_hash does not match the staged code"
+        );
+        assert!(
+            env::block_timestamp() >= staged_upgrade.executable_at,
+            "Upgrade can only be executed from {}",
+            staged_upgrade.executable_at
+        );
+        let code = env::storage_read(STAGED_CODE_KEY).expect("Staged code is missing");
+
+        let reserved_gas = env::used_gas() + GAS_FOR_UPGRADE + GAS_FOR_UPGRADE_CALLBACK;
+        let migrate_gas = env::prepaid_gas()
+            .checked_sub(reserved_gas)
+            .unwrap_or_else(|| {
+                env::panic(
+                    format!(
+                        "Upgrade needs more than {} gas to deploy and migrate",
+                        reserved_gas
+                    )
+                    .as_bytes(),
+                )
+            });
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(
+                b"migrate".to_vec(),
+                migrate_args
+                    .unwrap_or_else(|| String::from("{}"))
+                    .into_bytes(),
+                0,
+                migrate_gas,
+            )
+            .then(ext_upgrade::on_upgrade(
+                code_hash,
+                &env::current_account_id(),
+                0,
+                GAS_FOR_UPGRADE_CALLBACK,
+            ))
+    }
+
+    //drops the deployed code and returns its storage to the owner, the code stays
+    //staged when the upgrade failed or when it was staged again in the meantime
+    #[private]
+    pub fn on_upgrade(&mut self, code_hash: String) -> bool {
+        let success = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        let view = match &self.staged_upgrade {
+            Some(staged_upgrade) if hex_hash(&staged_upgrade.code_hash) == code_hash => {
+                staged_upgrade.view()
+            }
+            _ => return success,
+        };
+        if success {
+            self.staged_upgrade = None;
+            CoinFlipEvent::UpgradeDeployed(&view).emit();
+            self.internal_remove_staged_code();
+        } else {
+            CoinFlipEvent::UpgradeFailed(&view).emit();
+        }
+        success
+    }
+
+    pub fn get_staged_upgrade(&self) -> Option<StagedUpgradeView> {
+        self.staged_upgrade
+            .as_ref()
+            .map(|staged_upgrade| staged_upgrade.view())
+    }
+}
+
+impl SlotMachine {
+    //removes the staged code and refunds the storage it released to the owner
+    fn internal_remove_staged_code(&self) -> Promise {
+        let initial_storage_usage = env::storage_usage();
+        env::storage_remove(STAGED_CODE_KEY);
+        let released = initial_storage_usage.saturating_sub(env::storage_usage()) as u128
+            * env::storage_byte_cost();
+        Promise::new(self.owner_id.clone()).transfer(released)
+    }
+}
+
+fn hex_hash(hash: &[u8]) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
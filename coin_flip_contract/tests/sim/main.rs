@@ -8,6 +8,8 @@ pub use near_sdk::AccountId;
 near_sdk_sim::lazy_static_include::lazy_static_include_bytes! {
     COIN_BYTES => "./target/wasm32-unknown-unknown/release/classy_kangaroo_coin_flip.wasm",
     NFT_BYTES => "../nep_171/target/wasm32-unknown-unknown/release/non_fungible_token.wasm",
+    //first release, before the state was versioned, built from the baseline commit
+    COIN_V1_BYTES => "./tests/sim/res/classy_kangaroo_coin_flip_v1.wasm",
 }
 
 
@@ -17,9 +19,71 @@ const HOUSE_FEE: u128 = 500;
 const WIN_MULTIPLIER: u128 = 200_000;
 const FRACTIONAL_BASE: u128 = 100_000;
 const MIN_BALANCE_FRACTION: u128 = 100;
-const NFT_MAPPING_SIZE: u128 = 50;
 
 const GAS_ATTACHMENT: u64 = 300_000_000_000_000;
+const RESOLVE_DELAY_BLOCKS: u64 = 2;
+//minimum config_delay of the contract, staged upgrades wait for it
+const MIN_CONFIG_DELAY: u64 = 86_400_000_000_000;
+
+//places a bet, waits for the resolution delay and resolves it
+//returns the BetResult of the resolve_bet call
+fn play_and_resolve(root: &UserAccount, player: &UserAccount, contract_id: &AccountId, bet_type: bool, bet_size: u128) -> ExecutionResult {
+    player.call(
+        contract_id.clone(), 
+        "play", 
+        &json!({
+            "bet_type": bet_type,
+            "bet_size": bet_size.to_string()
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        0
+    ).assert_success();
+
+    root.borrow_runtime_mut().produce_blocks(RESOLVE_DELAY_BLOCKS).unwrap();
+
+    player.call(
+        contract_id.clone(), 
+        "resolve_bet", 
+        &json!({
+            "account_id": player.account_id()
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        0
+    )
+}
+
+//reads the size of the nft collection once its tokens are minted
+fn sync_nft_supply(caller: &UserAccount, contract_id: &AccountId) {
+    caller.call(
+        contract_id.clone(),
+        "sync_nft_supply",
+        &json!({}).to_string().into_bytes(),
+        GAS_ATTACHMENT,
+        0
+    ).assert_success();
+}
+
+//owner funds the house bankroll, bets are only accepted while it can pay out the win
+fn deposit_house_funds(owner: &UserAccount, contract_id: &AccountId, amount: u128) {
+    owner.call(
+        contract_id.clone(), 
+        "deposit_house_funds", 
+        &json!({}).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        amount
+    ).assert_success();
+}
+
+//registers account with the coin flip contract, required before depositing
+fn register_storage(account: &UserAccount, contract_id: &AccountId) {
+    account.call(
+        contract_id.clone(), 
+        "storage_deposit", 
+        &json!({}).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        to_yocto("0.2")
+    ).assert_success();
+}
 
 #[test]
 fn simulate_full_flow_1() {
@@ -74,18 +138,21 @@ fn simulate_full_flow_1() {
         coin_account.account_id(), 
         "new", 
         &json!({"owner_id": dev_account.account_id(),
-                "nft_fee": NFT_FEE.to_string(),
-                "dev_fee": DEV_FEE.to_string(),
-                "house_fee": HOUSE_FEE.to_string(),
-                "win_multiplier": WIN_MULTIPLIER.to_string(),
-                "base_gas": GAS_ATTACHMENT.to_string(),
-                "max_bet": max_bet.to_string(),
-                "min_bet": min_bet.to_string(),
-                "min_balance_fraction": MIN_BALANCE_FRACTION.to_string(),
+                "nft_id": nft_account.account_id(),
+                "config": {
+                    "nft_fee": NFT_FEE.to_string(),
+                    "dev_fee": DEV_FEE.to_string(),
+                    "house_fee": HOUSE_FEE.to_string(),
+                    "win_multiplier": WIN_MULTIPLIER.to_string(),
+                    "max_bet": max_bet.to_string(),
+                    "min_bet": min_bet.to_string(),
+                    "min_balance_fraction": MIN_BALANCE_FRACTION.to_string()
+                }
         }).to_string().into_bytes(),
         GAS_ATTACHMENT, 
         0
     ).assert_success();
+    deposit_house_funds(&dev_account, &coin_account.account_id(), to_yocto("50"));
 
     //user 1 gets 2 NFTs, user 2 gets 1, user 3 gets none
     let nft_mint_token = | account: String, token_id: String | {
@@ -105,6 +172,7 @@ fn simulate_full_flow_1() {
     nft_mint_token(consumer1.account_id(), "1".to_string());
     nft_mint_token(consumer1.account_id(), "2".to_string());
     nft_mint_token(consumer2.account_id(), "3".to_string());
+    sync_nft_supply(&dev_account, &coin_account.account_id());
     
     //user 3 will be responsible for playing. (1) deposit test, (2) play test, (3) withdrawal test
     //besides testing expected state changes, also test if state thet shouldn't mutate has done so
@@ -112,6 +180,7 @@ fn simulate_full_flow_1() {
     //deposit
     let deposit_amount = to_yocto("10");
 
+    register_storage(&consumer3, &coin_account.account_id());
     consumer3.call(
         coin_account.account_id(), 
         "deposit", 
@@ -179,16 +248,7 @@ fn simulate_full_flow_1() {
             }).to_string().into_bytes(),
         )).parse().unwrap();
     
-        game_result = consumer3.call(
-            coin_account.account_id(), 
-            "play", 
-            &json!({
-                "_bet_type": true,
-                "bet_size": bet_size.to_string()
-            }).to_string().into_bytes(),
-            GAS_ATTACHMENT, 
-            0
-        ).unwrap_json_value().as_bool().unwrap();
+        game_result = play_and_resolve(&root, &consumer3, &coin_account.account_id(), true, bet_size).unwrap_json_value()["won"].as_bool().unwrap();
     
         consumer_balance2 = ViewResult::unwrap_json::<String>(&consumer3.view(
             coin_account.account_id(), 
@@ -309,16 +369,42 @@ fn simulate_full_flow_1() {
     let initial_consumer2_near_balance: u128 = consumer2.account().unwrap().amount;
     let initial_consumer3_near_balance: u128 = consumer3.account().unwrap().amount;
 
+    let nft_share: u128 = ViewResult::unwrap_json::<String>(&consumer3.view(
+        coin_account.account_id(), 
+        "get_nft_claimable", 
+        &json!({
+            "token_id": "1"
+        }).to_string().into_bytes(),
+    )).parse().unwrap();
+    assert!(nft_share > 0);
+
+    dev_account.call(
+        coin_account.account_id(), 
+        "start_nft_distribution", 
+        &json!({}).to_string().into_bytes(), 
+        GAS_ATTACHMENT, 
+        1
+    ).assert_success();
+
     dev_account.call(
         coin_account.account_id(), 
         "retrieve_nft_funds", 
         &json!({
-            "distribution_list": vec![&consumer1.account_id, &consumer1.account_id, &consumer2.account_id]
+            "limit": 10
         }).to_string().into_bytes(), 
         GAS_ATTACHMENT, 
         1
     ).assert_success();
 
+    let distribution: Value = consumer3.view(
+        coin_account.account_id(), 
+        "get_nft_distribution", 
+        &json!({}).to_string().into_bytes(),
+    ).unwrap_json();
+    assert_eq!(distribution["finished"], true);
+    assert_eq!(distribution["processed"], "3");
+    assert_eq!(distribution["total_paid"], (3 * nft_share).to_string());
+
     let state_view1: std::collections::HashMap<String, String> = consumer3.view(
         coin_account.account_id(), 
         "get_contract_state", 
@@ -332,9 +418,9 @@ fn simulate_full_flow_1() {
     let final_consumer2_near_balance: u128 = consumer2.account().unwrap().amount;
     let final_consumer3_near_balance: u128 = consumer3.account().unwrap().amount;
 
-    assert_eq!(final_nft_funds, 0);
-    assert_eq!(final_consumer1_near_balance, initial_consumer1_near_balance + 2 * ( initial_nft_funds / 3 ) );
-    assert_eq!(final_consumer2_near_balance, initial_consumer2_near_balance + 1 * ( initial_nft_funds / 3 ) );
+    assert_eq!(final_nft_funds, initial_nft_funds - 3 * nft_share);
+    assert_eq!(final_consumer1_near_balance, initial_consumer1_near_balance + 2 * nft_share );
+    assert_eq!(final_consumer2_near_balance, initial_consumer2_near_balance + 1 * nft_share );
     assert_eq!(final_consumer3_near_balance, initial_consumer3_near_balance);
     assert_eq!(final_dev_funds, initial_dev_funds);
 }
@@ -393,19 +479,20 @@ fn simulate_full_flow_2() {
         "new", 
         &json!({"owner_id": dev_account.account_id(),
                 "nft_id": nft_account.account_id(),
-                "nft_fee": NFT_FEE.to_string(),
-                "dev_fee": DEV_FEE.to_string(),
-                "house_fee": HOUSE_FEE.to_string(),
-                "win_multiplier": WIN_MULTIPLIER.to_string(),
-                "base_gas": GAS_ATTACHMENT.to_string(),
-                "max_bet": max_bet.to_string(),
-                "min_bet": min_bet.to_string(),
-                "min_balance_fraction": MIN_BALANCE_FRACTION.to_string(),
-                "nft_mapping_size": NFT_MAPPING_SIZE.to_string()
+                "config": {
+                    "nft_fee": NFT_FEE.to_string(),
+                    "dev_fee": DEV_FEE.to_string(),
+                    "house_fee": HOUSE_FEE.to_string(),
+                    "win_multiplier": WIN_MULTIPLIER.to_string(),
+                    "max_bet": max_bet.to_string(),
+                    "min_bet": min_bet.to_string(),
+                    "min_balance_fraction": MIN_BALANCE_FRACTION.to_string()
+                }
         }).to_string().into_bytes(),
         GAS_ATTACHMENT, 
         0
     ).assert_success();
+    deposit_house_funds(&dev_account, &coin_account.account_id(), to_yocto("50"));
 
     //user 1 gets 2 NFTs, user 2 gets 1, user 3 gets none
     let nft_mint_token = | account: String, token_id: String | {
@@ -425,6 +512,7 @@ fn simulate_full_flow_2() {
     nft_mint_token(consumer1.account_id(), "1".to_string());
     nft_mint_token(consumer1.account_id(), "2".to_string());
     nft_mint_token(consumer2.account_id(), "3".to_string());
+    sync_nft_supply(&dev_account, &coin_account.account_id());
     
     //user 3 will be responsible for playing. (1) deposit test, (2) play test, (3) withdrawal test
     //besides testing expected state changes, also test if state thet shouldn't mutate has done so
@@ -432,6 +520,7 @@ fn simulate_full_flow_2() {
     //deposit
     let deposit_amount = to_yocto("10");
 
+    register_storage(&consumer3, &coin_account.account_id());
     consumer3.call(
         coin_account.account_id(), 
         "deposit", 
@@ -499,16 +588,7 @@ fn simulate_full_flow_2() {
             }).to_string().into_bytes(),
         )).parse().unwrap();
     
-        game_result = consumer3.call(
-            coin_account.account_id(), 
-            "play", 
-            &json!({
-                "_bet_type": true,
-                "bet_size": bet_size.to_string()
-            }).to_string().into_bytes(),
-            GAS_ATTACHMENT, 
-            0
-        ).unwrap_json_value().as_bool().unwrap();
+        game_result = play_and_resolve(&root, &consumer3, &coin_account.account_id(), true, bet_size).unwrap_json_value()["won"].as_bool().unwrap();
 
     
         consumer_balance2 = ViewResult::unwrap_json::<String>(&consumer3.view(
@@ -593,15 +673,43 @@ fn simulate_full_flow_2() {
     let initial_consumer2_near_balance: u128 = consumer2.account().unwrap().amount;
     let initial_consumer3_near_balance: u128 = consumer3.account().unwrap().amount;
 
-    dev_account.call(
+    let nft_share: u128 = ViewResult::unwrap_json::<String>(&consumer3.view(
         coin_account.account_id(), 
-        "retrieve_nft_funds", 
+        "get_nft_claimable", 
         &json!({
-            "distribution_list": vec![&consumer1.account_id, &consumer1.account_id, &consumer2.account_id]
+            "token_id": "1"
+        }).to_string().into_bytes(),
+    )).parse().unwrap();
+    assert!(nft_share > 0);
+
+    //token owners claim their own share
+    let claim_nft_rewards = | account: &UserAccount, token_id: &str | {
+        account.call(
+            coin_account.account_id(), 
+            "claim_nft_rewards", 
+            &json!({
+                "token_id": token_id
+            }).to_string().into_bytes(), 
+            GAS_ATTACHMENT, 
+            0
+        ).assert_success();
+    };
+
+    claim_nft_rewards(&consumer1, "1");
+    claim_nft_rewards(&consumer1, "2");
+    claim_nft_rewards(&consumer2, "3");
+
+    //claiming a token owned by someone else fails
+    let wrong_owner_result = consumer3.call(
+        coin_account.account_id(), 
+        "claim_nft_rewards", 
+        &json!({
+            "token_id": "1"
         }).to_string().into_bytes(), 
         GAS_ATTACHMENT, 
-        1
-    ).assert_success();
+        0
+    );
+    assert!(!wrong_owner_result.is_ok());
 
     let state_view1: std::collections::HashMap<String, String> = consumer3.view(
         coin_account.account_id(), 
@@ -616,9 +724,9 @@ fn simulate_full_flow_2() {
     let final_consumer2_near_balance: u128 = consumer2.account().unwrap().amount;
     let final_consumer3_near_balance: u128 = consumer3.account().unwrap().amount;
 
-    assert_eq!(final_nft_funds, 0);
-    assert_eq!(final_consumer1_near_balance, initial_consumer1_near_balance + 2 * ( initial_nft_funds / 3 ) );
-    assert_eq!(final_consumer2_near_balance, initial_consumer2_near_balance + 1 * ( initial_nft_funds / 3 ) );
+    assert_eq!(final_nft_funds, initial_nft_funds - 3 * nft_share);
+    assert_eq!(final_consumer1_near_balance, initial_consumer1_near_balance + 2 * nft_share );
+    assert_eq!(final_consumer2_near_balance, initial_consumer2_near_balance + 1 * nft_share );
     assert_eq!(final_consumer3_near_balance, initial_consumer3_near_balance);
     assert_eq!(final_dev_funds, initial_dev_funds);
     assert!(final_dev_funds > 0);
@@ -708,22 +816,25 @@ fn test_preservation_of_state_in_play_function() {
             "new", 
             &json!({"owner_id": dev_account.account_id(),
                     "nft_id": nft_account.account_id(),
-                    "nft_fee": NFT_FEE.to_string(),
-                    "dev_fee": DEV_FEE.to_string(),
-                    "house_fee": HOUSE_FEE.to_string(),
-                    "win_multiplier": WIN_MULTIPLIER.to_string(),
-                    "base_gas": GAS_ATTACHMENT.to_string(),
-                    "max_bet": max_bet.to_string(),
-                    "min_bet": min_bet.to_string(),
-                    "min_balance_fraction": MIN_BALANCE_FRACTION.to_string(),
-                    "nft_mapping_size": NFT_MAPPING_SIZE.to_string()
+                    "config": {
+                        "nft_fee": NFT_FEE.to_string(),
+                        "dev_fee": DEV_FEE.to_string(),
+                        "house_fee": HOUSE_FEE.to_string(),
+                        "win_multiplier": WIN_MULTIPLIER.to_string(),
+                        "max_bet": max_bet.to_string(),
+                        "min_bet": min_bet.to_string(),
+                        "min_balance_fraction": MIN_BALANCE_FRACTION.to_string()
+                    }
             }).to_string().into_bytes(),
             GAS_ATTACHMENT, 
             0
         ).assert_success();
+        deposit_house_funds(&dev_account, &coin_account.account_id(), to_yocto("50"));
         
         //deposit
         let call_deposit = |consumer: &UserAccount, deposit_amount: u128| {
+            //already registered accounts get the storage deposit refunded
+            register_storage(consumer, &coin_account.account_id());
             consumer.call(
                 coin_account.account_id(), 
                 "deposit", 
@@ -807,16 +918,7 @@ fn test_preservation_of_state_in_play_function() {
         let consumer2_balance4: u128 = view_balance(&consumer2).parse().unwrap();
         let consumer3_balance4: u128 = view_balance(&consumer3).parse().unwrap();
     
-        game_result = consumer3.call(
-            coin_account.account_id(), 
-            "play", 
-            &json!({
-                "_bet_type": true,
-                "bet_size": bet_size.to_string()
-            }).to_string().into_bytes(),
-            GAS_ATTACHMENT, 
-            0
-        ).unwrap_json_value().as_bool().unwrap();
+        game_result = play_and_resolve(&root, &consumer3, &coin_account.account_id(), true, bet_size).unwrap_json_value()["won"].as_bool().unwrap();
     
         let consumer1_balance5: u128 = view_balance(&consumer1).parse().unwrap();
         let consumer2_balance5: u128 = view_balance(&consumer2).parse().unwrap();
@@ -888,7 +990,7 @@ fn simulate_n_nft_holders() {
 
     let root = init_simulator(Some(genesis));
 
-    let dev_account = root.create_user("dev_account".to_string(), to_yocto("100"));
+    let dev_account = root.create_user("dev_account".to_string(), to_yocto("200000"));
     
     let consumer = root.create_user("consumer".to_string(), to_yocto("100000"));
 
@@ -922,19 +1024,20 @@ fn simulate_n_nft_holders() {
         "new", 
         &json!({"owner_id": dev_account.account_id(),
                 "nft_id": nft_account.account_id(),
-                "nft_fee": NFT_FEE.to_string(),
-                "dev_fee": DEV_FEE.to_string(),
-                "house_fee": HOUSE_FEE.to_string(),
-                "win_multiplier": WIN_MULTIPLIER.to_string(),
-                "base_gas": GAS_ATTACHMENT.to_string(),
-                "max_bet": max_bet.to_string(),
-                "min_bet": min_bet.to_string(),
-                "min_balance_fraction": MIN_BALANCE_FRACTION.to_string(),
-                "nft_mapping_size": NFT_MAPPING_SIZE.to_string()
+                "config": {
+                    "nft_fee": NFT_FEE.to_string(),
+                    "dev_fee": DEV_FEE.to_string(),
+                    "house_fee": HOUSE_FEE.to_string(),
+                    "win_multiplier": WIN_MULTIPLIER.to_string(),
+                    "max_bet": max_bet.to_string(),
+                    "min_bet": min_bet.to_string(),
+                    "min_balance_fraction": MIN_BALANCE_FRACTION.to_string()
+                }
         }).to_string().into_bytes(),
         GAS_ATTACHMENT, 
         0
     ).assert_success();
+    deposit_house_funds(&dev_account, &coin_account.account_id(), to_yocto("100000"));
 
     //user 1 gets 2 NFTs, user 2 gets 1, user 3 gets none
     let nft_mint_token = | account: String, token_id: u128 | {
@@ -960,15 +1063,11 @@ fn simulate_n_nft_holders() {
         nft_mint_token(account_id, counter);
         counter += 1;
     }
+    sync_nft_supply(&dev_account, &coin_account.account_id());
 
-    let mut account_arr: Vec<String> = Vec::<String>::new();
-
-    for item in account_vector.iter() {
-        account_arr.push(item.account_id.clone());
-    }
-    
     let deposit_amount = to_yocto("10000");
 
+    register_storage(&consumer, &coin_account.account_id());
     consumer.call(
         coin_account.account_id(), 
         "deposit", 
@@ -983,16 +1082,7 @@ fn simulate_n_nft_holders() {
 
     loop {
      
-        consumer.call(
-            coin_account.account_id(), 
-            "play", 
-            &json!({
-                "_bet_type": true,
-                "bet_size": bet_size.to_string()
-            }).to_string().into_bytes(),
-            GAS_ATTACHMENT, 
-            0
-        ).unwrap_json_value().as_bool().unwrap();
+        play_and_resolve(&root, &consumer, &coin_account.account_id(), true, bet_size).unwrap_json_value()["won"].as_bool().unwrap();
     
         consumer_balance = ViewResult::unwrap_json::<String>(&consumer.view(
             coin_account.account_id(), 
@@ -1017,7 +1107,14 @@ fn simulate_n_nft_holders() {
     ).unwrap_json();
 
     let nft_balance: u128 = retrieved_state.get("nft_balance").unwrap().parse().unwrap();
-    let ideal_share: u128 = nft_balance / (N - 1);
+    let ideal_share: u128 = ViewResult::unwrap_json::<String>(&consumer.view(
+        coin_account.account_id(), 
+        "get_nft_claimable", 
+        &json!({
+            "token_id": "1"
+        }).to_string().into_bytes(),
+    )).parse().unwrap();
+    assert_eq!(ideal_share, nft_balance / (N - 1));
 
     let get_near_balance = |account: &UserAccount| -> u128 {
         account.account().unwrap().amount
@@ -1028,15 +1125,38 @@ fn simulate_n_nft_holders() {
         initial_balances_vector.push(get_near_balance(item));
     }
 
+    //distribute in chunks of the nft enumeration to stay within gas limits
+    const CHUNK_SIZE: u64 = 25;
     dev_account.call(
         coin_account.account_id(), 
-        "retrieve_nft_funds", 
-        &json!({
-            "distribution_list": account_arr
-        }).to_string().into_bytes(), 
+        "start_nft_distribution", 
+        &json!({}).to_string().into_bytes(), 
         GAS_ATTACHMENT, 
         1
-    );
+    ).assert_success();
+
+    loop {
+        dev_account.call(
+            coin_account.account_id(), 
+            "retrieve_nft_funds", 
+            &json!({
+                "limit": CHUNK_SIZE
+            }).to_string().into_bytes(), 
+            GAS_ATTACHMENT, 
+            1
+        ).assert_success();
+
+        let distribution: Value = consumer.view(
+            coin_account.account_id(), 
+            "get_nft_distribution", 
+            &json!({}).to_string().into_bytes(),
+        ).unwrap_json();
+        if distribution["finished"].as_bool().unwrap() {
+            assert_eq!(distribution["processed"], (N - 1).to_string());
+            assert_eq!(distribution["failed_transfers"], "0");
+            break
+        }
+    }
 
     // println!("{:#?}", exe_result);
 
@@ -1053,4 +1173,405 @@ fn simulate_n_nft_holders() {
         index_counter += 1;
     }
 
-}
\ No newline at end of file
+}
+#[test]
+fn simulate_retrieve_credits_while_paused() {
+    //pausing the contract blocks deposits and plays
+    //but players must still be able to retrieve their credits
+
+    let mut genesis = near_sdk_sim::runtime::GenesisConfig::default();
+    genesis.gas_limit = u64::MAX;
+    genesis.gas_price = 0;
+
+    let root = init_simulator(Some(genesis));
+
+    let dev_account = root.create_user("dev_account".to_string(), to_yocto("100"));
+    let consumer1 = root.create_user("consumer1".to_string(), to_yocto("100"));
+
+    let nft_account = root.deploy(
+        &NFT_BYTES,
+        "nft_contract".to_string(),
+        to_yocto("100")
+    );
+
+    let coin_account = root.deploy(
+        &COIN_BYTES,
+        "coin_contract".to_string(),
+        to_yocto("100")
+    );
+
+    root.call(
+        nft_account.account_id(), 
+        "new_default_meta", 
+        &json!({
+            "owner_id": dev_account.account_id()
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        0
+    ).assert_success();
+
+    let max_bet: u128 = to_yocto("5");
+    let min_bet: u128 = to_yocto("0.05");
+    root.call(
+        coin_account.account_id(), 
+        "new", 
+        &json!({"owner_id": dev_account.account_id(),
+                "nft_id": nft_account.account_id(),
+                "config": {
+                    "nft_fee": NFT_FEE.to_string(),
+                    "dev_fee": DEV_FEE.to_string(),
+                    "house_fee": HOUSE_FEE.to_string(),
+                    "win_multiplier": WIN_MULTIPLIER.to_string(),
+                    "max_bet": max_bet.to_string(),
+                    "min_bet": min_bet.to_string(),
+                    "min_balance_fraction": MIN_BALANCE_FRACTION.to_string()
+                }
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        0
+    ).assert_success();
+
+    let deposit_amount = to_yocto("10");
+    register_storage(&consumer1, &coin_account.account_id());
+    consumer1.call(
+        coin_account.account_id(), 
+        "deposit", 
+        &json!({}).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        deposit_amount
+    ).assert_success();
+
+    //owner pauses everything
+    dev_account.call(
+        coin_account.account_id(), 
+        "pause", 
+        &json!({
+            "reason": "incident"
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        1
+    ).assert_success();
+
+    //deposits and plays are rejected
+    let deposit_result = consumer1.call(
+        coin_account.account_id(), 
+        "deposit", 
+        &json!({}).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        deposit_amount
+    );
+    assert!(!deposit_result.is_ok());
+
+    let play_result = consumer1.call(
+        coin_account.account_id(), 
+        "play", 
+        &json!({
+            "bet_type": true,
+            "bet_size": min_bet.to_string()
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        0
+    );
+    assert!(!play_result.is_ok());
+
+    //credits can still be retrieved
+    let near_balance0: u128 = consumer1.account().unwrap().amount;
+    consumer1.call(
+        coin_account.account_id(), 
+        "retrieve_credits", 
+        &json!({}).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        0
+    ).assert_success();
+    let near_balance1: u128 = consumer1.account().unwrap().amount;
+
+    let credits: String = consumer1.view(
+        coin_account.account_id(), 
+        "get_credits", 
+        &json!({
+            "account_id": consumer1.account_id()
+        }).to_string().into_bytes(),
+    ).unwrap_json();
+
+    assert_eq!(credits, "0");
+    assert_eq!(near_balance1, near_balance0 + deposit_amount);
+}
+
+#[test]
+fn simulate_migrate_from_v1() {
+    //deploy the first release, populate credits and fees,
+    //then upgrade to the current wasm and check that all balances survive
+
+    let mut genesis = near_sdk_sim::runtime::GenesisConfig::default();
+    genesis.gas_limit = u64::MAX;
+    genesis.gas_price = 0;
+
+    let root = init_simulator(Some(genesis));
+
+    let dev_account = root.create_user("dev_account".to_string(), to_yocto("100"));
+    let consumer1 = root.create_user("consumer1".to_string(), to_yocto("100"));
+    let consumer2 = root.create_user("consumer2".to_string(), to_yocto("100"));
+
+    let nft_account = root.deploy(
+        &NFT_BYTES,
+        "nft_contract".to_string(),
+        to_yocto("100")
+    );
+
+    let coin_account = root.deploy(
+        &COIN_V1_BYTES,
+        "coin_contract".to_string(),
+        to_yocto("100")
+    );
+
+    let max_bet: u128 = to_yocto("5");
+    let min_bet: u128 = to_yocto("0.05");
+    //V1 takes the settings as separate arguments and has no nft contract
+    root.call(
+        coin_account.account_id(), 
+        "new", 
+        &json!({"owner_id": dev_account.account_id(),
+                "nft_fee": NFT_FEE.to_string(),
+                "dev_fee": DEV_FEE.to_string(),
+                "house_fee": HOUSE_FEE.to_string(),
+                "win_multiplier": WIN_MULTIPLIER.to_string(),
+                "max_bet": max_bet.to_string(),
+                "min_bet": min_bet.to_string(),
+                "min_balance_fraction": MIN_BALANCE_FRACTION.to_string()
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        0
+    ).assert_success();
+
+    //V1 deposits need no storage registration and bets resolve right away
+    for (consumer, deposit_amount) in [(&consumer1, to_yocto("10")), (&consumer2, to_yocto("20"))].iter() {
+        consumer.call(
+            coin_account.account_id(), 
+            "deposit", 
+            &json!({}).to_string().into_bytes(),
+            GAS_ATTACHMENT, 
+            *deposit_amount
+        ).assert_success();
+    }
+    for _ in 0..5 {
+        consumer1.call(
+            coin_account.account_id(), 
+            "play", 
+            &json!({
+                "bet_type": true,
+                "bet_size": to_yocto("1").to_string()
+            }).to_string().into_bytes(),
+            GAS_ATTACHMENT, 
+            0
+        ).assert_success();
+    }
+
+    let view_credits = |consumer: &UserAccount| -> u128 {
+        let credits: String = consumer.view(
+            coin_account.account_id(), 
+            "get_credits", 
+            &json!({
+                "account_id": consumer.account_id()
+            }).to_string().into_bytes(),
+        ).unwrap_json();
+        credits.parse().unwrap()
+    };
+    let view_state = || -> Value {
+        root.view(
+            coin_account.account_id(), 
+            "get_contract_state", 
+            &json!({}).to_string().into_bytes(),
+        ).unwrap_json_value()
+    };
+
+    let consumer1_credits0 = view_credits(&consumer1);
+    let consumer2_credits0 = view_credits(&consumer2);
+    let state0 = view_state();
+    let total_credits = consumer1_credits0 + consumer2_credits0;
+
+    //upgrade, the contract account deploys the new wasm and migrates in the same transaction
+    coin_account.create_transaction(coin_account.account_id())
+        .deploy_contract(COIN_BYTES.to_vec())
+        .function_call(
+            "migrate".to_string(),
+            json!({
+                "from_v1": {
+                    "nft_id": nft_account.account_id(),
+                    "total_credits": total_credits.to_string()
+                }
+            }).to_string().into_bytes(),
+            GAS_ATTACHMENT,
+            0
+        )
+        .submit()
+        .assert_success();
+
+    let state1 = view_state();
+    assert_eq!(view_credits(&consumer1), consumer1_credits0);
+    assert_eq!(view_credits(&consumer2), consumer2_credits0);
+    assert_eq!(state1["owner_id"], state0["owner_id"]);
+    assert_eq!(state1["nft_balance"], state0["nft_balance"]);
+    assert_eq!(state1["dev_balance"], state0["dev_balance"]);
+    assert_eq!(state1["win_multiplier"], state0["win_multiplier"]);
+    //credits are only counted once their holders are indexed
+    assert_eq!(state1["total_credits"], "0");
+
+    //owner indexes the holders and finishes the migration once they add up
+    dev_account.call(
+        coin_account.account_id(),
+        "index_credit_holders",
+        &json!({
+            "account_ids": [consumer1.account_id(), consumer2.account_id()]
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT,
+        1
+    ).assert_success();
+    dev_account.call(
+        coin_account.account_id(),
+        "finish_v1_migration",
+        &json!({}).to_string().into_bytes(),
+        GAS_ATTACHMENT,
+        1
+    ).assert_success();
+    assert_eq!(view_state()["total_credits"], total_credits.to_string());
+
+    //migrate can only be called by the contract itself
+    let migrate_result = consumer1.call(
+        coin_account.account_id(), 
+        "migrate", 
+        &json!({}).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        0
+    );
+    assert!(!migrate_result.is_ok());
+
+    //players take their credits out of the upgraded contract
+    let near_balance0: u128 = consumer2.account().unwrap().amount;
+    consumer2.call(
+        coin_account.account_id(), 
+        "retrieve_credits", 
+        &json!({}).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        0
+    ).assert_success();
+    assert_eq!(consumer2.account().unwrap().amount, near_balance0 + consumer2_credits0);
+    assert_eq!(view_credits(&consumer2), 0);
+
+    //and keep playing after registering storage
+    register_storage(&consumer1, &coin_account.account_id());
+    play_and_resolve(&root, &consumer1, &coin_account.account_id(), true, to_yocto("1")).assert_success();
+}
+
+#[test]
+fn simulate_self_upgrade() {
+    //owner stages a release and the contract deploys it to itself and migrates,
+    //without any access key on the contract account being used
+
+    let mut genesis = near_sdk_sim::runtime::GenesisConfig::default();
+    genesis.gas_limit = u64::MAX;
+    genesis.gas_price = 0;
+    //a block per config_delay so the staged code can be deployed on the next one
+    genesis.block_prod_time = MIN_CONFIG_DELAY;
+
+    let root = init_simulator(Some(genesis));
+
+    let dev_account = root.create_user("dev_account".to_string(), to_yocto("100"));
+    let consumer1 = root.create_user("consumer1".to_string(), to_yocto("100"));
+
+    let nft_account = root.deploy(
+        &NFT_BYTES,
+        "nft_contract".to_string(),
+        to_yocto("100")
+    );
+
+    let coin_account = root.deploy(
+        &COIN_BYTES,
+        "coin_contract".to_string(),
+        to_yocto("100")
+    );
+
+    let max_bet: u128 = to_yocto("5");
+    let min_bet: u128 = to_yocto("0.05");
+    root.call(
+        coin_account.account_id(), 
+        "new", 
+        &json!({"owner_id": dev_account.account_id(),
+                "nft_id": nft_account.account_id(),
+                "config": {
+                    "nft_fee": NFT_FEE.to_string(),
+                    "dev_fee": DEV_FEE.to_string(),
+                    "house_fee": HOUSE_FEE.to_string(),
+                    "win_multiplier": WIN_MULTIPLIER.to_string(),
+                    "max_bet": max_bet.to_string(),
+                    "min_bet": min_bet.to_string(),
+                    "min_balance_fraction": MIN_BALANCE_FRACTION.to_string()
+                }
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        0
+    ).assert_success();
+    deposit_house_funds(&dev_account, &coin_account.account_id(), to_yocto("50"));
+
+    let deposit_amount = to_yocto("10");
+    register_storage(&consumer1, &coin_account.account_id());
+    consumer1.call(
+        coin_account.account_id(), 
+        "deposit", 
+        &json!({}).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        deposit_amount
+    ).assert_success();
+
+    //only the owner can stage code
+    let wrong_owner_result = consumer1.call(
+        coin_account.account_id(), 
+        "stage_upgrade", 
+        &json!({
+            "code": Base64VecU8(COIN_BYTES.to_vec())
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        to_yocto("10")
+    );
+    assert!(!wrong_owner_result.is_ok());
+
+    let staged = dev_account.call(
+        coin_account.account_id(), 
+        "stage_upgrade", 
+        &json!({
+            "code": Base64VecU8(COIN_BYTES.to_vec())
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        to_yocto("10")
+    );
+    staged.assert_success();
+    let code_hash = staged.unwrap_json_value()["code_hash"].as_str().unwrap().to_string();
+
+    root.borrow_runtime_mut().produce_blocks(1).unwrap();
+    dev_account.call(
+        coin_account.account_id(), 
+        "upgrade", 
+        &json!({
+            "code_hash": code_hash
+        }).to_string().into_bytes(),
+        GAS_ATTACHMENT, 
+        1
+    ).assert_success();
+
+    let staged_upgrade: Value = root.view(
+        coin_account.account_id(), 
+        "get_staged_upgrade", 
+        &json!({}).to_string().into_bytes(),
+    ).unwrap_json_value();
+    assert!(staged_upgrade.is_null());
+
+    let credits: String = consumer1.view(
+        coin_account.account_id(), 
+        "get_credits", 
+        &json!({
+            "account_id": consumer1.account_id()
+        }).to_string().into_bytes(),
+    ).unwrap_json();
+    assert_eq!(credits, deposit_amount.to_string());
+
+    play_and_resolve(&root, &consumer1, &coin_account.account_id(), true, to_yocto("1")).assert_success();
+}
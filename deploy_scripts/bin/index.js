@@ -3,7 +3,7 @@
 import yargs from 'yargs';
 import { hideBin } from 'yargs/helpers';
 import { deployContractAccountExists, deployContractNewAccount } from './../deploy/deploy_contract.js';
-import { initializeContract, emergencyPanic, getContractState, updateContract, retrieveDevFunds, retrieveNftFunds } from "./../deploy/call_contract_function.js"
+import { initializeContract, pauseContract, unpauseContract, emergencyWithdraw, getContractState, updateContract, retrieveDevFunds, retrieveNftFunds } from "./../deploy/call_contract_function.js"
 
 //import 
 
@@ -29,7 +29,7 @@ yargs(hideBin(process.argv))
         }
     )
     .command(
-        'initialize <ownerAccount> <contractAccount> <nftFee> <devFee> <houseFee> <winMultiplier> <maxBet> <minBet> <minBalanceFraction>', 'initialize state for deployed contract',
+        'initialize <ownerAccount> <contractAccount> <nftContractAccount> <nftFee> <devFee> <houseFee> <winMultiplier> <maxBet> <minBet> <minBalanceFraction>', 'initialize state for deployed contract',
         (yargs) => {
             yargs.positional(
                 'ownerAccount', {
@@ -43,6 +43,12 @@ yargs(hideBin(process.argv))
                     default: 'Cambi',
                     describe: 'account that hosts the contract'
                 });
+            yargs.positional(
+                'nftContractAccount', {
+                    type: 'string',
+                    default: 'Cambi',
+                    describe: 'account that hosts the nft contract'
+                });
             yargs.positional(
                 'nftFee', {
                     type: 'string',
@@ -112,7 +118,7 @@ yargs(hideBin(process.argv))
         }
     )
     .command(
-        'retrieve-nft <ownerAccount> <contractAccount> <nftContractAccount>', 'send cumulated tokens to nft holders',
+        'retrieve-nft <ownerAccount> <contractAccount>', 'send cumulated tokens to nft holders',
         (yargs) => {
             yargs.positional(
                 'ownerAccount', {
@@ -126,19 +132,59 @@ yargs(hideBin(process.argv))
                     default: 'Cambi',
                     describe: 'account that hosts the contract'
                 });
+        },
+        (argv) => {
+            retrieveNftFunds(argv.ownerAccount, argv.contractAccount);
+        }
+    )
+    .command(
+        'pause <ownerAccount> <contractAccount> <reason>', 'pause deposits, plays and withdrawals',
+        (yargs) => {
+            yargs.positional(
+                'ownerAccount', {
+                    type: 'string',
+                    default: 'Cambi',
+                    describe: 'account that will send the transaction'
+                });
             yargs.positional(
-                'nftContractAccount', {
+                'contractAccount', {
                     type: 'string',
                     default: 'Cambi',
-                    describe: 'account that hosts the nft contract'
+                    describe: 'account that hosts the contract'
+                });
+            yargs.positional(
+                'reason', {
+                    type: 'string',
+                    default: 'Cambi',
+                    describe: 'reason for pausing, shown in the error of rejected calls'
+                });
+        },
+        (argv) => {
+            pauseContract(argv.ownerAccount, argv.contractAccount, argv.reason);
+        }
+    )
+    .command(
+        'unpause <ownerAccount> <contractAccount>', 'resume all paused operations',
+        (yargs) => {
+            yargs.positional(
+                'ownerAccount', {
+                    type: 'string',
+                    default: 'Cambi',
+                    describe: 'account that will send the transaction'
+                });
+            yargs.positional(
+                'contractAccount', {
+                    type: 'string',
+                    default: 'Cambi',
+                    describe: 'account that hosts the contract'
                 });
         },
         (argv) => {
-            retrieveNftFunds(argv.ownerAccount, argv.contractAccount, argv.nftContractAccount);
+            unpauseContract(argv.ownerAccount, argv.contractAccount);
         }
     )
     .command(
-        'emergency-panic <ownerAccount> <contractAccount> <withdrawalAmount>', 'activate emergency panic mode',
+        'emergency-withdraw <ownerAccount> <contractAccount> <withdrawalAmount>', 'withdraw funds held above the contract liabilities',
         (yargs) => {
             yargs.positional(
                 'ownerAccount', {
@@ -160,7 +206,7 @@ yargs(hideBin(process.argv))
                 });
         },
         (argv) => {
-            emergencyPanic(argv.ownerAccount, argv.contractAccount, argv.withdrawalAmount);
+            emergencyWithdraw(argv.ownerAccount, argv.contractAccount, argv.withdrawalAmount);
         }
     )
     .command(
@@ -184,7 +230,7 @@ yargs(hideBin(process.argv))
         }
     )
     .command(
-        'update-state <ownerAccount> <contractAccount> <nftFee> <devFee> <houseFee> <winMultiplier> <maxBet> <minBet> <minBalanceFraction>', 'initialize state for deployed contract',
+        'update-state <ownerAccount> <contractAccount> <nftFee> <devFee> <houseFee> <winMultiplier> <maxBet> <minBet> <minBalanceFraction>', 'propose new state for deployed contract, applied after config_delay with execute_config',
         (yargs) => {
             yargs.positional(
                 'ownerAccount', {
@@ -8,10 +8,10 @@ async function buildContractObject(ownerAccount, contractAccount) {
     const contract = new nearAPI.Contract(
         account, // the account object that is connecting
         contractAccount, {
-            viewMethods: [],
+            viewMethods: ["get_nft_distribution"],
             changeMethods: [
-                "new", "retrieve_dev_funds", "retrieve_nft_funds",
-                "update_contract", "emergency_panic", "get_contract_state"
+                "new", "retrieve_dev_funds", "start_nft_distribution", "retrieve_nft_funds",
+                "propose_config", "pause", "unpause", "emergency_withdraw", "get_contract_state"
             ],
             sender: account, // account object to initialize and sign transactions.
         }
@@ -9,13 +9,16 @@ async function initializeContract(ownerAccount, contractAccount, params) {
 
     let namedArgs = {
         owner_id: ownerAccount,
-        nft_fee: params.nftFee,
-        dev_fee: params.devFee,
-        house_fee: params.houseFee,
-        win_multiplier: params.winMultiplier,
-        max_bet: params.maxBet,
-        min_bet: params.minBet,
-        min_balance_fraction: params.minBalanceFraction
+        nft_id: params.nftContractAccount,
+        config: {
+            nft_fee: params.nftFee,
+            dev_fee: params.devFee,
+            house_fee: params.houseFee,
+            win_multiplier: params.winMultiplier,
+            max_bet: params.maxBet,
+            min_bet: params.minBet,
+            min_balance_fraction: params.minBalanceFraction
+        }
     };
 
     const result = await contract.new(
@@ -27,12 +30,40 @@ async function initializeContract(ownerAccount, contractAccount, params) {
     return result;
 }
 
-//emergency_panic
-async function emergencyPanic(ownerAccount, contractAccount, withdrawalAmount) {
+//pause
+async function pauseContract(ownerAccount, contractAccount, reason) {
     const contract = await buildContractObject(ownerAccount, contractAccount);
 
-    const result = await contract.emergency_panic({
-            withdrawal_balance: withdrawalAmount.toString()
+    const result = await contract.pause({
+            reason: reason
+        },
+        "300000000000000",
+        "1"
+    );
+
+    console.log(result);
+    return result;
+}
+
+//unpause
+async function unpauseContract(ownerAccount, contractAccount) {
+    const contract = await buildContractObject(ownerAccount, contractAccount);
+
+    const result = await contract.unpause({},
+        "300000000000000",
+        "1"
+    );
+
+    console.log(result);
+    return result;
+}
+
+//emergency_withdraw
+async function emergencyWithdraw(ownerAccount, contractAccount, withdrawalAmount) {
+    const contract = await buildContractObject(ownerAccount, contractAccount);
+
+    const result = await contract.emergency_withdraw({
+            amount: withdrawalAmount.toString()
         },
         "300000000000000",
         "1"
@@ -54,21 +85,23 @@ async function getContractState(ownerAccount, contractAccount) {
     return result;
 }
 
-//update_contract
+//propose_config, settings can only change after config_delay
 async function updateContract(ownerAccount, contractAccount, params) {
     const contract = await buildContractObject(ownerAccount, contractAccount);
 
     let namedArgs = {
-        nft_fee: params.nftFee,
-        dev_fee: params.devFee,
-        house_fee: params.houseFee,
-        win_multiplier: params.winMultiplier,
-        max_bet: params.maxBet,
-        min_bet: params.minBet,
-        min_balance_fraction: params.minBalanceFraction
+        patch: {
+            nft_fee: params.nftFee,
+            dev_fee: params.devFee,
+            house_fee: params.houseFee,
+            win_multiplier: params.winMultiplier,
+            max_bet: params.maxBet,
+            min_bet: params.minBet,
+            min_balance_fraction: params.minBalanceFraction
+        }
     };
 
-    const result = await contract.update_contract(
+    const result = await contract.propose_config(
         namedArgs,
         "300000000000000",
         "1"
@@ -92,86 +125,33 @@ async function retrieveDevFunds(ownerAccount, contractAccount) {
 }
 
 //retrieve_nft_funds
-async function retrieveNftFunds(ownerAccount, contractAccount, nftContractAccount) {
+async function retrieveNftFunds(ownerAccount, contractAccount) {
     const contract = await buildContractObject(ownerAccount, contractAccount);
-    const { near, account } = await loginNear(ownerAccount);
-
-    //get nft count
-    const tokenCount = await account.viewFunction(nftContractAccount, "nft_total_supply", {});
-    let nftCount = parseInt(tokenCount);
-    console.log(tokenCount);
-
-    //get list of all hodlers
-    let pagination = 1;
-    let currentSize = 0;
-    let nftList = [];
-    let fetchResult;
-
-    let currentPagination = 0;
-
-    while (currentSize < nftCount) {
-        console.log(currentSize);
-        fetchResult = await account.viewFunction(nftContractAccount, "nft_token", { token_id: currentPagination.toString() });
-        currentPagination += 1;
-        if (fetchResult === null) {
-            continue
-        }
-        nftList.push(fetchResult);
-        // fetchResult = await account.viewFunction(nftContractAccount, "nft_tokens", { from_index: currentSize.toString(), limit: pagination })
-        // nftList.push(...fetchResult);
-        currentSize = nftList.length;
-    }
-    console.log(nftList);
-
-    //get current state
-    let contractState = await contract.get_contract_state({},
-        "300000000000000",
-    );
-    let nftBalance = contractState.nft_balance
-    console.log(nftBalance);
 
+    //snapshot fees accrued so far, unless a round is still in progress
+    let distribution = await contract.get_nft_distribution({});
+    if (distribution === null || distribution.finished) {
+        distribution = await contract.start_nft_distribution({},
+            "300000000000000",
+            "1"
+        );
+    }
 
-    //retrieve funds
-    await contract.retrieve_nft_funds({
-            distribution_list: [account.accountId]
-        },
-        "300000000000000",
-        "1"
-    );
-
-    //get value per account
-    let bnNftCount = new BN(tokenCount);
-    let bnNftBalance = new BN(nftBalance);
-    let idealShare = bnNftBalance.div(bnNftCount);
-    let idealShareString = idealShare.toString(10);
-
-    let receipt;
-    let receiptList = [];
-    let objectTransaction;
-
-    for (let holderAccount of nftList) {
-        console.log(`${holderAccount.token_id}/${tokenCount}`);
-        try {
-            receipt = await account.sendMoney(
-                holderAccount.owner_id, // receiver account
-                idealShareString // amount in yoctoNEAR
-            );
-        } catch (err) {
-            console.log(err);
-            console.log(receiptList);
-        }
-
-        objectTransaction = {
-            owner_id: holderAccount.owner_id,
-            nft_id: holderAccount.token_id,
-            transfer_value: idealShareString,
-            receipt: receipt.transaction.hash
-        }
-        receiptList.push(objectTransaction);
+    //distribute funds in chunks of the nft enumeration
+    const chunkSize = 25;
+    while (!distribution.finished) {
+        console.log(`${distribution.processed} tokens processed`);
+        await contract.retrieve_nft_funds({
+                limit: chunkSize
+            },
+            "300000000000000",
+            "1"
+        );
+        distribution = await contract.get_nft_distribution({});
     }
 
+    console.log(distribution);
     console.log("retrieval successfull");
-    console.log(JSON.stringify(receiptList));
 }
 
-export { initializeContract, emergencyPanic, getContractState, updateContract, retrieveDevFunds, retrieveNftFunds };
\ No newline at end of file
+export { initializeContract, pauseContract, unpauseContract, emergencyWithdraw, getContractState, updateContract, retrieveDevFunds, retrieveNftFunds };
\ No newline at end of file
@@ -8,11 +8,11 @@ async function buildContractObject(ownerAccount, contractAccount) {
     const contract = new nearAPI.Contract(
         account, // the account object that is connecting
         contractAccount, {
-            viewMethods: [],
+            viewMethods: ["get_nft_distribution", "storage_balance_of", "storage_balance_bounds"],
             changeMethods: [
-                "new", "retrieve_dev_funds", "retrieve_nft_funds",
-                "update_contract", "emergency_panic", "get_contract_state",
-                "deposit", "play", "nft_mint", "new_default_meta"
+                "new", "retrieve_dev_funds", "start_nft_distribution", "retrieve_nft_funds",
+                "propose_config", "pause", "unpause", "emergency_withdraw", "get_contract_state",
+                "storage_deposit", "deposit", "play", "resolve_bet", "nft_mint", "new_default_meta"
             ],
             sender: account, // account object to initialize and sign transactions.
         }
@@ -2,20 +2,22 @@ import nearAPI from "near-api-js";
 import loginNear from "./_login.js";
 
 import buildContractObject from "./_contract_object.js";
-import { BN } from "bn.js";
 
 async function initializeContract(ownerAccount, contractAccount, params) {
     const contract = await buildContractObject(ownerAccount, contractAccount);
     console.log(params);
     let namedArgs = {
         owner_id: ownerAccount,
-        nft_fee: params.nftFee,
-        dev_fee: params.devFee,
-        house_fee: params.houseFee,
-        win_multiplier: params.winMultiplier,
-        max_bet: params.maxBet,
-        min_bet: params.minBet,
-        min_balance_fraction: params.minBalanceFraction
+        nft_id: params.nftContractAccount,
+        config: {
+            nft_fee: params.nftFee,
+            dev_fee: params.devFee,
+            house_fee: params.houseFee,
+            win_multiplier: params.winMultiplier,
+            max_bet: params.maxBet,
+            min_bet: params.minBet,
+            min_balance_fraction: params.minBalanceFraction
+        }
     };
     console.log(namedArgs);
 
@@ -28,12 +30,40 @@ async function initializeContract(ownerAccount, contractAccount, params) {
     return result;
 }
 
-//emergency_panic
-async function emergencyPanic(ownerAccount, contractAccount, withdrawalAmount) {
+//pause
+async function pauseContract(ownerAccount, contractAccount, reason) {
+    const contract = await buildContractObject(ownerAccount, contractAccount);
+
+    const result = await contract.pause({
+            reason: reason
+        },
+        "300000000000000",
+        "1"
+    );
+
+    console.log(result);
+    return result;
+}
+
+//unpause
+async function unpauseContract(ownerAccount, contractAccount) {
+    const contract = await buildContractObject(ownerAccount, contractAccount);
+
+    const result = await contract.unpause({},
+        "300000000000000",
+        "1"
+    );
+
+    console.log(result);
+    return result;
+}
+
+//emergency_withdraw
+async function emergencyWithdraw(ownerAccount, contractAccount, withdrawalAmount) {
     const contract = await buildContractObject(ownerAccount, contractAccount);
 
-    const result = await contract.emergency_panic({
-            withdrawal_balance: withdrawalAmount.toString()
+    const result = await contract.emergency_withdraw({
+            amount: withdrawalAmount.toString()
         },
         "300000000000000",
         "1"
@@ -55,21 +85,23 @@ async function getContractState(ownerAccount, contractAccount) {
     return result;
 }
 
-//update_contract
+//propose_config, settings can only change after config_delay
 async function updateContract(ownerAccount, contractAccount, params) {
     const contract = await buildContractObject(ownerAccount, contractAccount);
 
     let namedArgs = {
-        nft_fee: params.nftFee,
-        dev_fee: params.devFee,
-        house_fee: params.houseFee,
-        win_multiplier: params.winMultiplier,
-        max_bet: params.maxBet,
-        min_bet: params.minBet,
-        min_balance_fraction: params.minBalanceFraction
+        patch: {
+            nft_fee: params.nftFee,
+            dev_fee: params.devFee,
+            house_fee: params.houseFee,
+            win_multiplier: params.winMultiplier,
+            max_bet: params.maxBet,
+            min_bet: params.minBet,
+            min_balance_fraction: params.minBalanceFraction
+        }
     };
 
-    const result = await contract.update_contract(
+    const result = await contract.propose_config(
         namedArgs,
         "300000000000000",
         "1"
@@ -93,109 +125,91 @@ async function retrieveDevFunds(ownerAccount, contractAccount) {
 }
 
 //retrieve_nft_funds
-async function retrieveNftFunds(ownerAccount, contractAccount, nftContractAccount) {
+async function retrieveNftFunds(ownerAccount, contractAccount) {
     const contract = await buildContractObject(ownerAccount, contractAccount);
-    const { near, account } = await loginNear(ownerAccount);
 
-    //get nft count
-    const tokenCount = await account.viewFunction(nftContractAccount, "nft_total_supply", {});
-    let nftCount = parseInt(tokenCount);
-    console.log(tokenCount);
-
-    //get list of all hodlers
-    let pagination = 1;
-    let currentSize = 0;
-    let nftList = [];
-    let fetchResult;
-
-    let currentPagination = 0;
-
-    while (currentSize < nftCount) {
-        console.log(currentSize);
-        fetchResult = await account.viewFunction(nftContractAccount, "nft_token", { token_id: currentPagination.toString() });
-        currentPagination += 1;
-        if (fetchResult === null) {
-            continue
-        }
-        nftList.push(fetchResult);
-        // fetchResult = await account.viewFunction(nftContractAccount, "nft_tokens", { from_index: currentSize.toString(), limit: pagination })
-        // nftList.push(...fetchResult);
-        currentSize = nftList.length;
+    //snapshot fees accrued so far, unless a round is still in progress
+    let distribution = await contract.get_nft_distribution({});
+    if (distribution === null || distribution.finished) {
+        distribution = await contract.start_nft_distribution({},
+            "300000000000000",
+            "1"
+        );
     }
-    console.log(nftList);
-
-    //get current state
-    let contractState = await contract.get_contract_state({},
-        "300000000000000",
-    );
-    let nftBalance = contractState.nft_balance
-    console.log(nftBalance);
 
+    //distribute funds in chunks of the nft enumeration
+    const chunkSize = 25;
+    while (!distribution.finished) {
+        console.log(`${distribution.processed} tokens processed`);
+        await contract.retrieve_nft_funds({
+                limit: chunkSize
+            },
+            "300000000000000",
+            "1"
+        );
+        distribution = await contract.get_nft_distribution({});
+    }
 
-    //retrieve funds
-    await contract.retrieve_nft_funds({
-            distribution_list: [account.accountId]
-        },
-        "300000000000000",
-        "1"
-    );
+    console.log(distribution);
+    console.log("retrieval successfull");
+}
 
-    //get value per account
-    let bnNftCount = new BN(tokenCount);
-    let bnNftBalance = new BN(nftBalance);
-    let idealShare = bnNftBalance.div(bnNftCount);
-    let idealShareString = idealShare.toString(10);
-
-    let receipt;
-    let receiptList = [];
-    let objectTransaction;
-
-    for (let holderAccount of nftList) {
-        console.log(`${holderAccount.token_id}/${tokenCount}`);
-        try {
-            receipt = await account.sendMoney(
-                holderAccount.owner_id, // receiver account
-                idealShareString // amount in yoctoNEAR
-            );
-        } catch (err) {
-            console.log(err);
-            console.log(receiptList);
-        }
+//registers the account with the storage deposit the contract asks for
+async function registerAccount(ownerAccount, contractAccount) {
+    const contract = await buildContractObject(ownerAccount, contractAccount);
 
-        objectTransaction = {
-            owner_id: holderAccount.owner_id,
-            nft_id: holderAccount.token_id,
-            transfer_value: idealShareString,
-            receipt: receipt.transaction.hash
-        }
-        receiptList.push(objectTransaction);
+    const storageBalance = await contract.storage_balance_of({
+        account_id: ownerAccount
+    });
+    if (storageBalance !== null) {
+        return storageBalance;
     }
 
-    console.log("retrieval successfull");
-    console.log(JSON.stringify(receiptList));
+    const bounds = await contract.storage_balance_bounds({});
+    return await contract.storage_deposit({},
+        "300000000000000",
+        bounds.min
+    );
 }
 
 async function deposit(ownerAccount, contractAccount) {
     const contract = await buildContractObject(ownerAccount, contractAccount);
-    const { near, account } = await loginNear(ownerAccount);
 
+    await registerAccount(ownerAccount, contractAccount);
     await contract.deposit({},
         "300000000000000",
         nearAPI.utils.format.parseNearAmount("50")
     );
 }
 
+//places a bet and resolves it once its block delay has passed
 async function play(ownerAccount, contractAccount, choice, value) {
     const contract = await buildContractObject(ownerAccount, contractAccount);
     const { near, account } = await loginNear(ownerAccount);
 
-    const result = await contract.play({
-            _bet_type: choice,
+    const pendingBet = await contract.play({
+            bet_type: choice,
             bet_size: nearAPI.utils.format.parseNearAmount(value)
         },
         "300000000000000",
         "0"
     );
+
+    let block = await near.connection.provider.block({ finality: "final" });
+    while (block.header.height < parseInt(pendingBet.resolvable_from)) {
+        await new Promise((resolve) => setTimeout(resolve, 1000));
+        block = await near.connection.provider.block({ finality: "final" });
+    }
+
+    const result = await contract.resolve_bet({
+            account_id: ownerAccount
+        },
+        "300000000000000",
+        "0"
+    );
+
+    console.log(result);
+    return result;
 }
 
 async function initializeNft(nftContract, sender) {
@@ -224,4 +238,4 @@ async function mintNft(nftContract, sender, receiver, id) {
     );
 }
 
-export { initializeContract, emergencyPanic, getContractState, updateContract, retrieveDevFunds, retrieveNftFunds, deposit, play, mintNft, initializeNft };
\ No newline at end of file
+export { initializeContract, pauseContract, unpauseContract, emergencyWithdraw, getContractState, updateContract, retrieveDevFunds, retrieveNftFunds, registerAccount, deposit, play, mintNft, initializeNft };
\ No newline at end of file
@@ -1,5 +1,5 @@
 import { deployContractAccountExists, deployContractNewAccount } from './deploy_contract.js';
-import { initializeContract, emergencyPanic, getContractState, updateContract, retrieveDevFunds, retrieveNftFunds, deposit, play, mintNft, initializeNft } from "./call_contract_function.js";
+import { initializeContract, pauseContract, unpauseContract, emergencyWithdraw, getContractState, updateContract, retrieveDevFunds, retrieveNftFunds, registerAccount, deposit, play, mintNft, initializeNft } from "./call_contract_function.js";
 
 // //deploy nft
 // await deployContractAccountExists("coinfliptest-nft.testnet", "/home/jveiga/CKcoinFlip/coin_flip/nep_171/target/wasm32-unknown-unknown/release/non_fungible_token.wasm");
@@ -25,6 +25,7 @@ import { initializeContract, emergencyPanic, getContractState, updateContract, r
 
 //initialize coin flip
 // let params = {
+//     nftContractAccount: "coinfliptest-nft.testnet",
 //     nftFee: "4000",
 //     devFee: "500",
 //     houseFee: "500",
@@ -46,6 +47,6 @@ import { initializeContract, emergencyPanic, getContractState, updateContract, r
 // console.log(await getContractState("coinfliptest-dev.testnet", "coinfliptest-contract.testnet"));
 
 // //withdraw
-// retrieveNftFunds("coinfliptest-dev.testnet", "coinfliptest-contract.testnet", "coinfliptest-nft.testnet");
+// retrieveNftFunds("coinfliptest-dev.testnet", "coinfliptest-contract.testnet");
 
 // nft balance
\ No newline at end of file
@@ -0,0 +1,56 @@
+//! NEP-297 event logs for the mint and transfer paths, following the NEP-171 events standard.
+use near_contract_standards::non_fungible_token::TokenId;
+use near_sdk::serde::Serialize;
+use near_sdk::{env, serde_json};
+
+pub const NFT_STANDARD_NAME: &str = "nep171";
+pub const NFT_STANDARD_VERSION: &str = "1.0.0";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a NftEvent<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum NftEvent<'a> {
+    NftMint(Vec<NftMintData<'a>>),
+    NftTransfer(Vec<NftTransferData<'a>>),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMintData<'a> {
+    pub owner_id: &'a str,
+    pub token_ids: Vec<&'a TokenId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftTransferData<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<&'a str>,
+    pub old_owner_id: &'a str,
+    pub new_owner_id: &'a str,
+    pub token_ids: Vec<&'a TokenId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+
+impl NftEvent<'_> {
+    pub fn emit(&self) {
+        let log = EventLog {
+            standard: NFT_STANDARD_NAME,
+            version: NFT_STANDARD_VERSION,
+            event: self,
+        };
+        env::log(format!("EVENT_JSON:{}", serde_json::to_string(&log).unwrap()).as_bytes());
+    }
+}
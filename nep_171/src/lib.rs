@@ -15,6 +15,9 @@ NOTES:
   - To prevent the deployed contract from being modified or deleted, it should not have any access
     keys on its account.
 */
+use near_contract_standards::non_fungible_token::core::{
+    NonFungibleTokenCore, NonFungibleTokenResolver,
+};
 use near_contract_standards::non_fungible_token::metadata::{
     NFTContractMetadata, NonFungibleTokenMetadataProvider, TokenMetadata, NFT_METADATA_SPEC,
 };
@@ -24,9 +27,14 @@ use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::LazyOption;
 use near_sdk::json_types::ValidAccountId;
 use near_sdk::{
-    env, near_bindgen, AccountId, BorshStorageKey, PanicOnDefault, Promise, PromiseOrValue,
+    assert_one_yocto, env, near_bindgen, AccountId, BorshStorageKey, PanicOnDefault, Promise,
+    PromiseOrValue,
 };
 
+mod events;
+
+use events::{NftEvent, NftMintData, NftTransferData};
+
 near_sdk::setup_alloc!();
 
 #[near_bindgen]
@@ -45,6 +53,7 @@ enum StorageKey {
     TokenMetadata,
     Enumeration,
     Approval,
+    PendingOwner,
 }
 
 #[near_bindgen]
@@ -98,11 +107,199 @@ impl Contract {
         receiver_id: ValidAccountId,
         token_metadata: TokenMetadata,
     ) -> Token {
-        self.tokens.mint(token_id, receiver_id, Some(token_metadata))
+        self.mint(token_id, receiver_id, Some(token_metadata))
+    }
+
+    /// First step of moving the minting rights to `new_owner_id`, which only takes effect
+    /// once `new_owner_id` calls `accept_ownership`. A new proposal replaces the previous one.
+    #[payable]
+    pub fn propose_owner(&mut self, new_owner_id: ValidAccountId) {
+        self.assert_owner();
+        assert_one_yocto();
+        assert!(
+            new_owner_id.as_ref() != &self.tokens.owner_id,
+            "{} is already the owner",
+            new_owner_id.as_ref()
+        );
+        pending_owner_id().set(new_owner_id.as_ref());
+    }
+
+    /// Makes the caller the owner of the contract, it must be the proposed owner.
+    #[payable]
+    pub fn accept_ownership(&mut self) {
+        assert_one_yocto();
+        let new_owner_id = env::predecessor_account_id();
+        assert!(
+            pending_owner_id().get() == Some(new_owner_id.clone()),
+            "Only the proposed owner can accept ownership"
+        );
+        self.tokens.owner_id = new_owner_id;
+        pending_owner_id().remove();
+    }
+
+    #[payable]
+    pub fn cancel_owner_proposal(&mut self) {
+        self.assert_owner();
+        assert_one_yocto();
+        assert!(
+            pending_owner_id().remove(),
+            "There is no ownership proposal"
+        );
+    }
+
+    /// Account allowed to mint tokens.
+    pub fn get_owner(&self) -> AccountId {
+        self.tokens.owner_id.clone()
+    }
+
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        pending_owner_id().get()
+    }
+}
+
+/// Account proposed as the next owner. Kept under its own key rather than in `Contract`
+/// so contracts deployed before ownership transfers keep their state layout.
+fn pending_owner_id() -> LazyOption<AccountId> {
+    LazyOption::new(StorageKey::PendingOwner, None)
+}
+
+impl Contract {
+    fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.tokens.owner_id,
+            "Unauthorized"
+        );
+    }
+
+    /// Logs a NEP-171 `nft_transfer` event for `token_id`. `authorized_id` is only set when
+    /// the transfer was made by an approved account instead of the owner.
+    fn emit_transfer(
+        &self,
+        old_owner_id: &AccountId,
+        new_owner_id: &AccountId,
+        token_id: &TokenId,
+        memo: Option<&str>,
+    ) {
+        let predecessor_id = env::predecessor_account_id();
+        let authorized_id = if &predecessor_id != old_owner_id {
+            Some(predecessor_id.as_str())
+        } else {
+            None
+        };
+        NftEvent::NftTransfer(vec![NftTransferData {
+            authorized_id,
+            old_owner_id,
+            new_owner_id,
+            token_ids: vec![token_id],
+            memo,
+        }])
+        .emit();
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenCore for Contract {
+    #[payable]
+    fn nft_transfer(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) {
+        let old_owner_id = self.tokens.owner_by_id.get(&token_id);
+        self.tokens
+            .nft_transfer(receiver_id.clone(), token_id.clone(), approval_id, memo.clone());
+        self.emit_transfer(
+            &old_owner_id.unwrap(),
+            receiver_id.as_ref(),
+            &token_id,
+            memo.as_deref(),
+        );
+    }
+
+    #[payable]
+    fn nft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let old_owner_id = self.tokens.owner_by_id.get(&token_id);
+        let result = self.tokens.nft_transfer_call(
+            receiver_id.clone(),
+            token_id.clone(),
+            approval_id,
+            memo.clone(),
+            msg,
+        );
+        self.emit_transfer(
+            &old_owner_id.unwrap(),
+            receiver_id.as_ref(),
+            &token_id,
+            memo.as_deref(),
+        );
+        result
+    }
+
+    fn nft_token(self, token_id: TokenId) -> Option<Token> {
+        self.tokens.nft_token(token_id)
+    }
+
+    /// Logs a NEP-171 `nft_mint` event, `nft_mint` mints through this method as well.
+    fn mint(
+        &mut self,
+        token_id: TokenId,
+        token_owner_id: ValidAccountId,
+        token_metadata: Option<TokenMetadata>,
+    ) -> Token {
+        let token = self.tokens.mint(token_id, token_owner_id, token_metadata);
+        NftEvent::NftMint(vec![NftMintData {
+            owner_id: &token.owner_id,
+            token_ids: vec![&token.token_id],
+            memo: None,
+        }])
+        .emit();
+        token
+    }
+}
+
+#[near_bindgen]
+impl NonFungibleTokenResolver for Contract {
+    /// Logs the token going back to `previous_owner_id` when the receiver of
+    /// `nft_transfer_call` asked for it to be returned.
+    #[private]
+    fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approved_account_ids: Option<std::collections::HashMap<AccountId, u64>>,
+    ) -> bool {
+        let transferred = self.tokens.nft_resolve_transfer(
+            previous_owner_id.clone(),
+            receiver_id.clone(),
+            token_id.clone(),
+            approved_account_ids,
+        );
+        let returned = self.tokens.owner_by_id.get(&token_id) == Some(previous_owner_id.clone());
+        if !transferred && returned {
+            NftEvent::NftTransfer(vec![NftTransferData {
+                authorized_id: None,
+                old_owner_id: &receiver_id,
+                new_owner_id: &previous_owner_id,
+                token_ids: vec![&token_id],
+                memo: None,
+            }])
+            .emit();
+        }
+        transferred
     }
 }
 
-near_contract_standards::impl_non_fungible_token_core!(Contract, tokens);
 near_contract_standards::impl_non_fungible_token_approval!(Contract, tokens);
 near_contract_standards::impl_non_fungible_token_enumeration!(Contract, tokens);
 
@@ -115,9 +312,10 @@ impl NonFungibleTokenMetadataProvider for Contract {
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
     use near_sdk::testing_env;
     use near_sdk::MockedBlockchain;
+    use std::collections::HashMap;
 
     use super::*;
 
@@ -153,7 +351,7 @@ mod tests {
     fn test_new() {
         let mut context = get_context(accounts(1));
         testing_env!(context.build());
-        let contract = Contract::new_default_meta(accounts(1).into());
+        let contract = Contract::new_default_meta(accounts(1));
         testing_env!(context.is_view(true).build());
         assert_eq!(contract.nft_token("1".to_string()), None);
     }
@@ -170,7 +368,7 @@ mod tests {
     fn test_mint() {
         let mut context = get_context(accounts(0));
         testing_env!(context.build());
-        let mut contract = Contract::new_default_meta(accounts(0).into());
+        let mut contract = Contract::new_default_meta(accounts(0));
 
         testing_env!(context
             .storage_usage(env::storage_usage())
@@ -184,13 +382,17 @@ mod tests {
         assert_eq!(token.owner_id, accounts(0).to_string());
         assert_eq!(token.metadata.unwrap(), sample_token_metadata());
         assert_eq!(token.approved_account_ids.unwrap(), HashMap::new());
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_mint","data":[{"owner_id":"alice","token_ids":["0"]}]}"#]
+        );
     }
 
     #[test]
     fn test_transfer() {
         let mut context = get_context(accounts(0));
         testing_env!(context.build());
-        let mut contract = Contract::new_default_meta(accounts(0).into());
+        let mut contract = Contract::new_default_meta(accounts(0));
 
         testing_env!(context
             .storage_usage(env::storage_usage())
@@ -206,6 +408,13 @@ mod tests {
             .predecessor_account_id(accounts(0))
             .build());
         contract.nft_transfer(accounts(1), token_id.clone(), None, None);
+        assert_eq!(
+            get_logs(),
+            vec![
+                "Transfer 0 from alice to bob",
+                r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_transfer","data":[{"old_owner_id":"alice","new_owner_id":"bob","token_ids":["0"]}]}"#
+            ]
+        );
 
         testing_env!(context
             .storage_usage(env::storage_usage())
@@ -227,7 +436,7 @@ mod tests {
     fn test_approve() {
         let mut context = get_context(accounts(0));
         testing_env!(context.build());
-        let mut contract = Contract::new_default_meta(accounts(0).into());
+        let mut contract = Contract::new_default_meta(accounts(0));
 
         testing_env!(context
             .storage_usage(env::storage_usage())
@@ -258,7 +467,7 @@ mod tests {
     fn test_revoke() {
         let mut context = get_context(accounts(0));
         testing_env!(context.build());
-        let mut contract = Contract::new_default_meta(accounts(0).into());
+        let mut contract = Contract::new_default_meta(accounts(0));
 
         testing_env!(context
             .storage_usage(env::storage_usage())
@@ -296,7 +505,7 @@ mod tests {
     fn test_revoke_all() {
         let mut context = get_context(accounts(0));
         testing_env!(context.build());
-        let mut contract = Contract::new_default_meta(accounts(0).into());
+        let mut contract = Contract::new_default_meta(accounts(0));
 
         testing_env!(context
             .storage_usage(env::storage_usage())
@@ -329,4 +538,66 @@ mod tests {
             .build());
         assert!(!contract.nft_is_approved(token_id.clone(), accounts(1), Some(1)));
     }
+
+    #[test]
+    fn test_ownership_transfer() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+
+        testing_env!(context.attached_deposit(1).build());
+        contract.propose_owner(accounts(1));
+        assert_eq!(contract.get_pending_owner(), Some(accounts(1).to_string()));
+        assert_eq!(contract.get_owner(), accounts(0).to_string());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build());
+        contract.accept_ownership();
+        assert_eq!(contract.get_owner(), accounts(1).to_string());
+        assert_eq!(contract.get_pending_owner(), None);
+
+        // the new owner mints
+        testing_env!(context
+            .storage_usage(env::storage_usage())
+            .attached_deposit(MINT_STORAGE_COST)
+            .predecessor_account_id(accounts(1))
+            .build());
+        let token = contract.nft_mint("0".to_string(), accounts(0), sample_token_metadata());
+        assert_eq!(token.owner_id, accounts(0).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the proposed owner can accept ownership")]
+    fn test_cancel_owner_proposal() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+
+        testing_env!(context.attached_deposit(1).build());
+        contract.propose_owner(accounts(1));
+        contract.cancel_owner_proposal();
+        assert_eq!(contract.get_pending_owner(), None);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build());
+        contract.accept_ownership();
+    }
+
+    #[test]
+    #[should_panic(expected = "Unauthorized")]
+    fn test_propose_owner_panic_not_owner() {
+        let mut context = get_context(accounts(0));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(0));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(1)
+            .build());
+        contract.propose_owner(accounts(1));
+    }
 }